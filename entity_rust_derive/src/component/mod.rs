@@ -0,0 +1,57 @@
+/// #[derive(Component)] generates the module `component!` would, with the derived
+/// type as its `Component`:
+///
+/// #[derive(Component)]
+/// pub struct Position { pub x: i64, pub y: i64 }
+///
+/// Expands to:
+///
+/// pub mod position {
+///   pub type Component = super::Position;
+///   component_storage!{}
+///   pub fn add_with_lock(list, entity, x: i64, y: i64) { .. }
+/// }
+///
+/// Types without named fields get no `add_with_lock`, they are added with `add`.
+///
+use proc_macro2::TokenStream;
+use syn::{ Data, DataStruct, DeriveInput, Error, Fields, Result };
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+	::check_public(&input.vis, &input.ident, "component")?;
+
+	if !input.generics.params.is_empty() {
+		return Err(Error::new_spanned(&input.generics, "generic types can not derive `Component`, register an instantiation instead, like `component!{ health: super::Health<u32> }`"));
+	}
+
+	let name = &input.ident;
+	let module = ::module_ident(name);
+
+	let add_with_lock = match input.data {
+		Data::Struct(DataStruct { fields: Fields::Named(ref fields), .. }) => {
+			let names : Vec<_> = fields.named.iter().map(|f| f.ident.clone().expect("Named field without a name")).collect();
+			let types = fields.named.iter().map(|f| &f.ty);
+			quote! {
+				pub fn add_with_lock(mut list: MappedSharedMutexWriteGuard<ComponentList<Component>>, entity: EntityID, #(#names : #types),*) {
+					let c = Component { #(#names : #names),* };
+					list.push((entity, c));
+				}
+			}
+		},
+		Data::Union(ref data) => return Err(Error::new_spanned(data.union_token, "unions can not derive `Component`")),
+		_ => quote!{}
+	};
+
+	Ok(quote! {
+		pub mod #module {
+			#[allow(unused_imports)]
+			use super::*;
+
+			pub type Component = super::#name;
+
+			::entity_rust::component_storage!{}
+
+			#add_with_lock
+		}
+	})
+}
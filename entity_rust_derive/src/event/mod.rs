@@ -0,0 +1,70 @@
+/// #[event] keeps the struct it is put on and generates the module `event!` would,
+/// with the struct as its `Data`:
+///
+/// #[event(coalesce = Latest)]
+/// pub struct MouseMove { pub x: i64, pub y: i64 }
+///
+/// Expands to:
+///
+/// pub struct MouseMove { pub x: i64, pub y: i64 }
+///
+/// pub mod mouse_move {
+///   pub type Data = super::MouseMove;
+///   event_body!{ x: i64, y: i64 ; coalesce = Latest }
+/// }
+///
+use proc_macro2::TokenStream;
+use syn::{ Error, Expr, Fields, ItemStruct, MetaNameValue, Result, Token };
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+
+pub fn expand(args: TokenStream, item: ItemStruct) -> Result<TokenStream> {
+	let policy = parse_args(args)?;
+
+	::check_public(&item.vis, &item.ident, "event")?;
+
+	if !item.generics.params.is_empty() {
+		return Err(Error::new_spanned(&item.generics, "events can not be generic, every event has a single queue of data"));
+	}
+
+	let fields : Vec<&syn::Field> = match item.fields {
+		Fields::Named(ref fields) => fields.named.iter().collect(),
+		Fields::Unit => vec![],
+		Fields::Unnamed(ref fields) => return Err(Error::new_spanned(fields, "event fields must be named, they are the arguments of `trigger`"))
+	};
+	let names = fields.iter().map(|f| &f.ident);
+	let types = fields.iter().map(|f| &f.ty);
+
+	let name = &item.ident;
+	let module = ::module_ident(name);
+
+	Ok(quote! {
+		#item
+
+		pub mod #module {
+			#[allow(unused_imports)]
+			use super::*;
+
+			/// The data of a single trigger.
+			pub type Data = super::#name;
+
+			::entity_rust::event_body!{ #(#names : #types),* ; coalesce = #policy }
+		}
+	})
+}
+
+/// The only argument is the coalescing policy, which defaults to `All`.
+fn parse_args(args: TokenStream) -> Result<TokenStream> {
+	let mut policy = quote!{ All };
+	let options = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse2(args)?;
+
+	for option in options {
+		if !option.path.is_ident("coalesce") {
+			return Err(Error::new_spanned(&option.path, "unknown event option, expected `coalesce = <policy>`"));
+		}
+		let value : &Expr = &option.value;
+		policy = quote!{ #value };
+	}
+
+	Ok(policy)
+}
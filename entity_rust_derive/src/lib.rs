@@ -0,0 +1,115 @@
+//! Attribute and derive macros that generate the same modules as the `component!`,
+//! `event!` and `system!` macros of entity_rust, but parse their input with syn so
+//! mistakes are reported at the offending token with a readable message.
+//!
+//! #[derive(Component)]
+//! pub struct Position { pub x: i64, pub y: i64 }
+//!
+//! #[event(coalesce = Latest)]
+//! pub struct MouseMove { pub x: i64, pub y: i64 }
+//!
+//! #[system]
+//! pub mod movement {
+//!   use super::{ mouse_move, position };
+//!
+//!   pub struct State { pub moves: i64 }
+//!
+//!   pub fn default_state() -> State { State { moves: 0 } }
+//!
+//!   impl State {
+//!     #[on]
+//!     fn mouse_move(&mut self, data: &[mouse_move::Data], #[write] positions: position) {
+//!       self.moves += data.len() as i64;
+//!     }
+//!   }
+//! }
+//!
+//! The generated code refers to the `entity_rust` crate, so it has to be a dependency
+//! of the crate that uses these macros.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+
+mod component;
+mod event;
+mod system;
+
+/// Registers a `pub` struct or enum as a component. The component module is named
+/// after the type in snake case, so `RigidBody` is stored in `rigid_body::LIST`.
+#[proc_macro_derive(Component)]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+	match syn::parse(input) {
+		Ok(input) => component::derive(input).unwrap_or_else(compile_error).into(),
+		Err(e) => compile_error(e).into()
+	}
+}
+
+/// Turns a `pub` struct into the data of an event, the event module is named after
+/// the struct in snake case. Takes an optional `coalesce = <policy>` argument.
+#[proc_macro_attribute]
+pub fn event(args: TokenStream, input: TokenStream) -> TokenStream {
+	match syn::parse(input) {
+		Ok(input) => event::expand(args.into(), input).unwrap_or_else(compile_error).into(),
+		Err(e) => compile_error(e).into()
+	}
+}
+
+/// Turns an inline module into a system. Methods of `impl State` marked with `#[on]`
+/// or `#[on_sync]` become the handlers of the event they are named after.
+#[proc_macro_attribute]
+pub fn system(args: TokenStream, input: TokenStream) -> TokenStream {
+	match syn::parse(input) {
+		Ok(input) => system::expand(args.into(), input).unwrap_or_else(compile_error).into(),
+		Err(e) => compile_error(e).into()
+	}
+}
+
+/// Converts a type name to the name of its module, `RigidBody` becomes `rigid_body`.
+fn snake_case(name: &str) -> String {
+	let chars : Vec<char> = name.chars().collect();
+	let mut snake = String::new();
+	for (i, &c) in chars.iter().enumerate() {
+		if c.is_uppercase() {
+			let after_lower = i > 0 && (chars[i - 1].is_lowercase() || chars[i - 1].is_numeric());
+			let before_lower = i > 0 && chars[i - 1].is_uppercase() && chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+			if after_lower || before_lower {
+				snake.push('_');
+			}
+			snake.extend(c.to_lowercase());
+		} else {
+			snake.push(c);
+		}
+	}
+	snake
+}
+
+/// Types that get a module need to be `pub`, the module exposes them.
+fn check_public(vis: &syn::Visibility, ident: &syn::Ident, what: &str) -> syn::Result<()> {
+	match *vis {
+		syn::Visibility::Public(_) => Ok(()),
+		_ => Err(syn::Error::new_spanned(ident, format!("{} `{}` must be `pub`, it is exposed by the generated `{}` module", what, ident, snake_case(&ident.to_string()))))
+	}
+}
+
+fn module_ident(ident: &syn::Ident) -> syn::Ident {
+	syn::Ident::new(&snake_case(&ident.to_string()), ident.span())
+}
+
+
+/// Reports the errors at their spans. `syn::Error::to_compile_error` invokes
+/// `::core::compile_error!`, and `::core` can not be named from the root of a 2015
+/// edition crate, so the path is dropped. Its tokens carry the first and last span of
+/// the offending tokens, which makes the error underline all of them.
+fn compile_error(error: syn::Error) -> proc_macro2::TokenStream {
+	error.into_iter().flat_map(|e| {
+		e.to_compile_error().into_iter().skip_while(|token| match *token {
+			proc_macro2::TokenTree::Ident(ref ident) => ident != "compile_error",
+			_ => true
+		})
+	}).collect()
+}
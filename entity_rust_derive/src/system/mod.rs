@@ -0,0 +1,324 @@
+/// #[system] turns an inline module into the module `system!` would generate. The
+/// state is a `pub struct State`, initialized by `default_state()` or its `Default`
+/// impl. Its methods marked with `#[on]` handle the event they are named after, the
+/// components they use follow the data argument, `#[write]` ones are locked mutably:
+///
+/// #[system]
+/// pub mod my_system {
+///   use super::{ my_event, position };
+///
+///   #[derive(Default)]
+///   pub struct State { pub x: i64 }
+///
+///   impl State {
+///     #[on]
+///     fn my_event(&mut self, data: &[my_event::Data], #[write] positions: position) {
+///       self.x += data[0].x;
+///     }
+///   }
+/// }
+///
+/// Expands to the same `on!` and `system_register!` invocations as the following,
+/// except that the handler takes the `#[write]` guards as `&mut` so it can change
+/// the components:
+///
+/// system!( my_system {
+///   on my_event, { positions: position }, {}, (self, data) => { self.x += data[0].x; }
+/// });
+///
+/// Methods marked with `#[on_sync]` handle sync events, they take the event argument.
+///
+use proc_macro2::TokenStream;
+use syn::{ Attribute, Block, Error, FnArg, Ident, ImplItem, Item, ItemImpl, ItemMod, Pat, Result, Type };
+use syn::spanned::Spanned;
+use syn::token::SelfValue;
+
+struct Handler {
+	event: Ident,
+	self_token: SelfValue,
+	data: Ident,
+	mut_components: Vec<(Ident, Ident)>,
+	components: Vec<(Ident, Ident)>,
+	body: Block
+}
+
+struct SyncHandler {
+	event: Ident,
+	self_token: SelfValue,
+	data: Ident,
+	body: Block
+}
+
+enum Kind {
+	On,
+	OnSync
+}
+
+pub fn expand(args: TokenStream, mut item: ItemMod) -> Result<TokenStream> {
+	if !args.is_empty() {
+		return Err(Error::new_spanned(args, "`#[system]` takes no arguments"));
+	}
+
+	let (_, contents) = match item.content.take() {
+		Some(contents) => contents,
+		None => return Err(Error::new_spanned(&item, "`#[system]` needs an inline module, like `mod my_system { .. }`"))
+	};
+
+	let mut items = vec![];
+	let mut handlers : Vec<Handler> = vec![];
+	let mut sync_handlers : Vec<SyncHandler> = vec![];
+	let mut state = None;
+	let mut has_default_state = false;
+
+	for content in contents {
+		match content {
+			Item::Struct(ref s) if s.ident == "State" => {
+				::check_public(&s.vis, &s.ident, "system state")?;
+				state = Some(s.ident.clone());
+			},
+			Item::Fn(ref f) if f.sig.ident == "default_state" => {
+				has_default_state = true;
+			},
+			Item::Fn(ref f) => {
+				if let Some(attr) = f.attrs.iter().find(|a| handler_kind(a).is_some()) {
+					return Err(Error::new_spanned(attr, "handlers are methods of `impl State`, move this function into it"));
+				}
+			},
+			_ => {}
+		}
+
+		match content {
+			Item::Impl(i) => items.push(Item::Impl(take_handlers(i, &mut handlers, &mut sync_handlers)?)),
+			other => items.push(other)
+		}
+	}
+
+	let state_definition = match state {
+		Some(_) => quote!{},
+		None => quote! {
+			#[derive(Default)]
+			pub struct State {}
+		}
+	};
+
+	let default_state = if has_default_state {
+		quote!{}
+	} else {
+		let span = state.map_or_else(|| item.ident.span(), |s| s.span());
+		quote_spanned! { span=>
+			pub fn default_state() -> State {
+				Default::default()
+			}
+		}
+	};
+
+	let ons = handlers.iter().map(|h| {
+		let Handler { ref event, ref self_token, ref data, ref body, .. } = *h;
+		let mut_names = h.mut_components.iter().map(|c| &c.0);
+		let mut_types = h.mut_components.iter().map(|c| &c.1);
+		let names = h.components.iter().map(|c| &c.0);
+		let types = h.components.iter().map(|c| &c.1);
+		quote! {
+			::entity_rust::on!{ @guards (#event, { #(#mut_names : #mut_types [mut])* }, { #(#names : #types)* }) #self_token, #data => #body }
+		}
+	});
+
+	let on_syncs = sync_handlers.iter().map(|h| {
+		let SyncHandler { ref event, ref self_token, ref data, ref body } = *h;
+		quote! {
+			::entity_rust::on_sync!{ (#event, #self_token, #data) #body }
+		}
+	});
+
+	let declarations = handlers.iter().map(|h| {
+		let event = &h.event;
+		let mut_names = h.mut_components.iter().map(|c| &c.0);
+		let mut_types = h.mut_components.iter().map(|c| &c.1);
+		let names = h.components.iter().map(|c| &c.0);
+		let types = h.components.iter().map(|c| &c.1);
+		quote! { (#event, { #(#mut_names : #mut_types)* }, { #(#names : #types)* }) }
+	});
+	let sync_events = sync_handlers.iter().map(|h| &h.event);
+
+	let attrs = &item.attrs;
+	let vis = &item.vis;
+	let name = &item.ident;
+
+	Ok(quote! {
+		#(#attrs)*
+		#vis mod #name {
+			use std::any::Any;
+			use ::entity_rust::shared_mutex::{ SharedMutex, MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
+			#[allow(unused_imports)]
+			use ::entity_rust::entities::{ ComponentList };
+			use std::sync::Arc;
+
+			#(#items)*
+
+			#state_definition
+			#default_state
+
+			#(#ons)*
+			#(#on_syncs)*
+
+			::entity_rust::system_register!{ (#(#declarations),*) (#(#sync_events),*) }
+		}
+	})
+}
+
+fn handler_kind(attr: &Attribute) -> Option<Kind> {
+	if attr.path().is_ident("on") {
+		Some(Kind::On)
+	} else if attr.path().is_ident("on_sync") {
+		Some(Kind::OnSync)
+	} else {
+		None
+	}
+}
+
+/// Removes the handler methods from an `impl State` block.
+fn take_handlers(mut block: ItemImpl, handlers: &mut Vec<Handler>, sync_handlers: &mut Vec<SyncHandler>) -> Result<ItemImpl> {
+	let is_state = block.trait_.is_none() && match *block.self_ty {
+		Type::Path(ref p) => p.qself.is_none() && p.path.is_ident("State"),
+		_ => false
+	};
+
+	let mut kept = vec![];
+	for impl_item in block.items {
+		let method = match impl_item {
+			ImplItem::Fn(method) => method,
+			other => {
+				kept.push(other);
+				continue;
+			}
+		};
+
+		let kind = match method.attrs.iter().filter_map(handler_kind).next() {
+			Some(kind) => kind,
+			None => {
+				kept.push(ImplItem::Fn(method));
+				continue;
+			}
+		};
+
+		if !is_state {
+			return Err(Error::new_spanned(&block.self_ty, "handlers are methods of `impl State`"));
+		}
+
+		let event = method.sig.ident.clone();
+		if handlers.iter().map(|h| &h.event).chain(sync_handlers.iter().map(|h| &h.event)).any(|e| *e == event) {
+			return Err(Error::new_spanned(&event, format!("the system already handles `{}`", event)));
+		}
+
+		for attr in method.attrs.iter() {
+			if handler_kind(attr).is_some() {
+				attr.meta.require_path_only().map_err(|_| Error::new_spanned(attr, "handler attributes take no arguments, handlers are named after their event"))?;
+			} else {
+				return Err(Error::new_spanned(attr, "handlers can not have other attributes"));
+			}
+		}
+
+		let sig = &method.sig;
+		if !sig.generics.params.is_empty() {
+			return Err(Error::new_spanned(&sig.generics, "handlers can not be generic"));
+		}
+		if let syn::ReturnType::Type(_, ref typ) = sig.output {
+			return Err(Error::new_spanned(typ, "handlers can not return a value"));
+		}
+		if let Some(ref asyncness) = sig.asyncness {
+			return Err(Error::new_spanned(asyncness, "handlers can not be async"));
+		}
+
+		let mut inputs = sig.inputs.iter();
+		let self_token = match inputs.next() {
+			Some(FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_some() => r.self_token,
+			Some(other) => return Err(Error::new_spanned(other, "handlers take `&mut self` as their first argument")),
+			None => return Err(Error::new(sig.paren_token.span.join(), "handlers take `&mut self` and the event data as arguments"))
+		};
+
+		let data = match inputs.next() {
+			Some(FnArg::Typed(arg)) => {
+				if let Some(attr) = arg.attrs.first() {
+					return Err(Error::new_spanned(attr, "the event data argument can not have attributes"));
+				}
+				argument_name(&arg.pat)?
+			},
+			Some(other) => return Err(Error::new_spanned(other, "expected the event data argument")),
+			None => return Err(Error::new(sig.paren_token.span.join(), format!("handlers take the event data as their second argument, like `data: &[{}::Data]`", event)))
+		};
+
+		match kind {
+			Kind::OnSync => {
+				if let Some(extra) = inputs.next() {
+					return Err(Error::new_spanned(extra, "sync handlers only take `&mut self` and the event argument"));
+				}
+				sync_handlers.push(SyncHandler { event, self_token, data, body: method.block });
+			},
+			Kind::On => {
+				let mut mut_components : Vec<(Ident, Ident)> = vec![];
+				let mut components : Vec<(Ident, Ident)> = vec![];
+
+				for input in inputs {
+					let arg = match *input {
+						FnArg::Typed(ref arg) => arg,
+						FnArg::Receiver(ref r) => return Err(Error::new_spanned(r, "expected a component argument"))
+					};
+
+					let mut write = false;
+					for attr in arg.attrs.iter() {
+						if attr.path().is_ident("write") {
+							write = true;
+						} else if !attr.path().is_ident("read") {
+							return Err(Error::new_spanned(attr, "component arguments can be marked `#[read]` or `#[write]`"));
+						}
+						attr.meta.require_path_only().map_err(|_| Error::new_spanned(attr, "`#[read]` and `#[write]` take no arguments"))?;
+					}
+
+					let name = argument_name(&arg.pat)?;
+					let module = component_module(&arg.ty)?;
+
+					if let Some(other) = mut_components.iter().chain(components.iter()).find(|c| c.1 == module) {
+						return Err(Error::new_spanned(&arg.ty, format!("`{}` is already used by `{}`, a handler can only lock a component once", module, other.0)));
+					}
+
+					if write {
+						mut_components.push((name, module));
+					} else {
+						components.push((name, module));
+					}
+				}
+
+				handlers.push(Handler {
+					event,
+					self_token,
+					data,
+					mut_components,
+					components,
+					body: method.block
+				});
+			}
+		}
+	}
+
+	block.items = kept;
+	Ok(block)
+}
+
+fn argument_name(pat: &Pat) -> Result<Ident> {
+	match *pat {
+		Pat::Ident(ref p) if p.by_ref.is_none() && p.subpat.is_none() => Ok(p.ident.clone()),
+		_ => Err(Error::new_spanned(pat, "handler arguments must be plain names"))
+	}
+}
+
+/// Components are named by their module, which has to be in scope of the system.
+fn component_module(typ: &Type) -> Result<Ident> {
+	if let Type::Path(ref p) = *typ {
+		if p.qself.is_none() {
+			if let Some(ident) = p.path.get_ident() {
+				return Ok(ident.clone());
+			}
+		}
+	}
+	Err(Error::new(typ.span(), "components are named by their module, like `positions: position`, import the module into the system with `use`"))
+}
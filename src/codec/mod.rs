@@ -0,0 +1,330 @@
+/// A compact binary encoding for event data, components and system state. It is used
+/// wherever the framework needs to write values out, like the event recorder.
+///
+/// Structs can implement the codec by listing their fields with the `codec!` macro:
+///
+/// event!{ mouse_move, x: i64, y: i64 }
+/// codec!{ mouse_move::Data { x, y } }
+///
+/// Numbers are written little endian, lengths are written as u64.
+use std::any::Any;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+pub trait Codec: Sized {
+	fn encode(&self, out: &mut Vec<u8>);
+	fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+	UnexpectedEnd,
+	Invalid(String)
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			DecodeError::UnexpectedEnd => write!(f, "Unexpected end of input while decoding"),
+			DecodeError::Invalid(ref reason) => write!(f, "Invalid input while decoding: {}", reason)
+		}
+	}
+}
+
+impl Error for DecodeError {}
+
+/// Decodes a value for a registry that only knows its type through the functions
+/// stored with it.
+pub type DecodeAnyFn = fn(&mut &[u8]) -> Result<Box<Any>, DecodeError>;
+
+/// A way of turning values into bytes. Registries store functions that are generic
+/// over the encoding, so they work for `Codec` types and, with the `serde` feature,
+/// for serde types.
+pub trait Encoding<T> {
+	fn encode(value: &T, out: &mut Vec<u8>);
+	fn decode(input: &mut &[u8]) -> Result<T, DecodeError>;
+}
+
+/// Encodes values with their `Codec` implementation.
+pub struct CodecEncoding;
+
+impl<T: Codec> Encoding<T> for CodecEncoding {
+	fn encode(value: &T, out: &mut Vec<u8>) {
+		value.encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<T, DecodeError> {
+		T::decode(input)
+	}
+}
+
+/// Encodes a value into a new buffer.
+pub fn to_bytes<T: Codec>(value: &T) -> Vec<u8> {
+	let mut out = vec![];
+	value.encode(&mut out);
+	out
+}
+
+/// Decodes a value from a buffer, failing if the buffer is not used up completely.
+pub fn from_bytes<T: Codec>(bytes: &[u8]) -> Result<T, DecodeError> {
+	let mut input = bytes;
+	let value = T::decode(&mut input)?;
+	if !input.is_empty() {
+		return Err(DecodeError::Invalid(format!("{} trailing bytes", input.len())));
+	}
+	Ok(value)
+}
+
+pub fn take<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8], DecodeError> {
+	if input.len() < count {
+		return Err(DecodeError::UnexpectedEnd);
+	}
+	let (taken, rest) = input.split_at(count);
+	*input = rest;
+	Ok(taken)
+}
+
+macro_rules! number_codec {
+	( $( $typ:ty ),* ) => ( $(
+		impl Codec for $typ {
+			fn encode(&self, out: &mut Vec<u8>) {
+				out.extend_from_slice(&self.to_le_bytes());
+			}
+
+			fn decode(input: &mut &[u8]) -> Result<$typ, DecodeError> {
+				let mut bytes = [0u8; ::std::mem::size_of::<$typ>()];
+				let len = bytes.len();
+				bytes.copy_from_slice(take(input, len)?);
+				Ok(<$typ>::from_le_bytes(bytes))
+			}
+		}
+	)* )
+}
+
+number_codec!{ u8, u16, u32, u64, i8, i16, i32, i64, f32, f64 }
+
+impl Codec for usize {
+	fn encode(&self, out: &mut Vec<u8>) {
+		(*self as u64).encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<usize, DecodeError> {
+		Ok(u64::decode(input)? as usize)
+	}
+}
+
+impl Codec for isize {
+	fn encode(&self, out: &mut Vec<u8>) {
+		(*self as i64).encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<isize, DecodeError> {
+		Ok(i64::decode(input)? as isize)
+	}
+}
+
+impl Codec for bool {
+	fn encode(&self, out: &mut Vec<u8>) {
+		out.push(*self as u8);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<bool, DecodeError> {
+		match u8::decode(input)? {
+			0 => Ok(false),
+			1 => Ok(true),
+			byte => Err(DecodeError::Invalid(format!("{} is not a bool", byte)))
+		}
+	}
+}
+
+impl Codec for () {
+	fn encode(&self, _: &mut Vec<u8>) {}
+
+	fn decode(_: &mut &[u8]) -> Result<(), DecodeError> {
+		Ok(())
+	}
+}
+
+impl Codec for String {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.len().encode(out);
+		out.extend_from_slice(self.as_bytes());
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<String, DecodeError> {
+		let len = usize::decode(input)?;
+		let bytes = take(input, len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|e| DecodeError::Invalid(e.to_string()))
+	}
+}
+
+impl Codec for Duration {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.as_secs().encode(out);
+		self.subsec_nanos().encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<Duration, DecodeError> {
+		let secs = u64::decode(input)?;
+		let nanos = u32::decode(input)?;
+		Ok(Duration::new(secs, nanos))
+	}
+}
+
+impl<T: Codec> Codec for Vec<T> {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.len().encode(out);
+		for value in self.iter() {
+			value.encode(out);
+		}
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<Vec<T>, DecodeError> {
+		let len = usize::decode(input)?;
+		let mut values = Vec::with_capacity(len.min(input.len()));
+		for _ in 0..len {
+			values.push(T::decode(input)?);
+		}
+		Ok(values)
+	}
+}
+
+impl<T: Codec + Ord> Codec for BTreeSet<T> {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.len().encode(out);
+		for value in self.iter() {
+			value.encode(out);
+		}
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<BTreeSet<T>, DecodeError> {
+		let len = usize::decode(input)?;
+		let mut values = BTreeSet::new();
+		for _ in 0..len {
+			values.insert(T::decode(input)?);
+		}
+		Ok(values)
+	}
+}
+
+impl<T: Codec> Codec for Option<T> {
+	fn encode(&self, out: &mut Vec<u8>) {
+		match *self {
+			Some(ref value) => {
+				true.encode(out);
+				value.encode(out);
+			},
+			None => false.encode(out)
+		}
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<Option<T>, DecodeError> {
+		if bool::decode(input)? {
+			Ok(Some(T::decode(input)?))
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+macro_rules! tuple_codec {
+	( $( $name:ident ),* ) => (
+		impl<$( $name: Codec ),*> Codec for ($( $name, )*) {
+			#[allow(non_snake_case)]
+			fn encode(&self, out: &mut Vec<u8>) {
+				let ($( ref $name, )*) = *self;
+				$( $name.encode(out); )*
+			}
+
+			fn decode(input: &mut &[u8]) -> Result<($( $name, )*), DecodeError> {
+				Ok(($( $name::decode(input)?, )*))
+			}
+		}
+	)
+}
+
+tuple_codec!{ A }
+tuple_codec!{ A, B }
+tuple_codec!{ A, B, C }
+tuple_codec!{ A, B, C, D }
+
+/// Implements `Codec` for a struct by encoding the listed fields in order.
+#[macro_export]
+macro_rules! codec {
+	( $typ:path { $( $field:ident ),* } ) => (
+		impl $crate::codec::Codec for $typ {
+			#[allow(unused_variables)]
+			fn encode(&self, out: &mut Vec<u8>) {
+				$( $crate::codec::Codec::encode(&self.$field, out); )*
+			}
+
+			#[allow(unused_variables)]
+			fn decode(input: &mut &[u8]) -> Result<Self, $crate::codec::DecodeError> {
+				Ok(Self {
+					$( $field: $crate::codec::Codec::decode(input)? ),*
+				})
+			}
+		}
+	)
+}
+
+/// Sorts the leading attributes of the struct defining macros before calling them back
+/// with `@attributes [attributes] [items] [default derives]` and the rest of the body.
+/// The items go next to the struct. A `#[derive]` drops the default derives and
+/// `#[serde]` is replaced by the serde derives.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! struct_attributes {
+	( $mac:ident [$($attr:tt)*] [$($item:tt)*] [$($default:tt)*] #[serde] $($rest:tt)* ) => (
+		$crate::serde_attributes!{ $mac [$($attr)*] [$($item)*] [$($default)*] $($rest)* }
+	);
+
+	( $mac:ident [$($attr:tt)*] [$($item:tt)*] [$($default:tt)*] #[derive $($derive:tt)*] $($rest:tt)* ) => (
+		$crate::struct_attributes!{ $mac [$($attr)* #[derive $($derive)*]] [$($item)*] [] $($rest)* }
+	);
+
+	( $mac:ident [$($attr:tt)*] [$($item:tt)*] [$($default:tt)*] #[$($other:tt)*] $($rest:tt)* ) => (
+		$crate::struct_attributes!{ $mac [$($attr)* #[$($other)*]] [$($item)*] [$($default)*] $($rest)* }
+	);
+
+	( $mac:ident [$($attr:tt)*] [$($item:tt)*] [$($default:tt)*] $($rest:tt)* ) => (
+		$crate::$mac!{ @attributes [$($attr)*] [$($item)*] [$($default)*] $($rest)* }
+	)
+}
+
+/// Derives the serde traits on the struct of a component, event or system state,
+/// `struct_attributes!` forwards `#[serde]` to this. The derives name the traits
+/// through an alias of this crate's serde, so users do not need their own serde
+/// dependency and can rename this crate.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! serde_attributes {
+	( $mac:ident [$($attr:tt)*] [$($item:tt)*] [$($default:tt)*] $($rest:tt)* ) => (
+		$crate::struct_attributes!{
+			$mac
+			[
+				$($attr)*
+				#[derive($crate::serde::Serialize, $crate::serde::Deserialize)]
+				#[serde(crate = "self::__entity_rust_serde")]
+			]
+			[
+				$($item)*
+				use $crate::serde as __entity_rust_serde;
+			]
+			[$($default)*]
+			$($rest)*
+		}
+	)
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! serde_attributes {
+	( $($body:tt)* ) => (
+		compile_error!("#[serde] requires the serde feature of entity_rust");
+	)
+}
@@ -6,11 +6,25 @@
 ///
 /// component! { physics, body: physics.RigidBody, physics_id: physics.ID }
 ///
+/// Lists whose `Storage` implements `Codec` can register a codec, so snapshots can
+/// save and restore them:
+///
+/// codec!{ position::Component { x, y } }
+/// components::register_codec::<position::List>();
+///
 
 use std::collections::{ HashMap };
 //use std::ops::CoerceUnsized;
 use std::any::{ Any, TypeId };
+use std::sync::Arc;
 use shared_mutex::{ SharedMutex, MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
+use codec::{ Codec, CodecEncoding, DecodeAnyFn, DecodeError, Encoding };
+#[cfg(feature = "serde")]
+use serde::{ Serialize, de::DeserializeOwned };
+#[cfg(feature = "serde")]
+use serialization::SerdeEncoding;
+use entities::{ ComponentList, EntityID, TagSet };
+use events;
 
 pub trait MappedSharedMutexGetters {
 	fn read_as_any<'mutex>(&self) -> MappedSharedMutexReadGuard<'mutex, Any>;
@@ -19,11 +33,111 @@ pub trait MappedSharedMutexGetters {
 
 pub struct Component {
 	pub name: TypeId,
-	pub getters: Box<MappedSharedMutexGetters+Sync>
+	/// The module path of the component, it identifies the list in snapshots.
+	pub label: &'static str,
+	pub getters: Box<MappedSharedMutexGetters+Sync>,
+	/// Removes the components of an entity from the list.
+	pub remove: fn(EntityID)
+}
+
+/// Implemented by the `List` type of every component module, it gives generic code
+/// access to the list of the module.
+pub trait ListType: 'static {
+	type Component: Any;
+	type Storage: EntityStorage + Any + Send + Sync;
+
+	fn label() -> &'static str;
+	fn list() -> &'static SharedMutex<Self::Storage>;
+}
+
+/// Storage that can be read and replaced one entity at a time, so changes can be
+/// tracked per entity.
+pub trait EntityStorage {
+	type Item;
+
+	/// Every entity in the storage with its items, sorted by entity.
+	fn entities(&self) -> Vec<(EntityID, Vec<&Self::Item>)>;
+	/// Whether the entity has any items in the storage.
+	fn has_entity(&self, entity: EntityID) -> bool;
+	/// Replaces the items of the entity, no items removes the entity.
+	fn set_entity(&mut self, entity: EntityID, items: Vec<Self::Item>);
+}
+
+impl<C> EntityStorage for ComponentList<C> {
+	type Item = C;
+
+	fn entities<'a>(&'a self) -> Vec<(EntityID, Vec<&'a C>)> {
+		let mut entities : Vec<(EntityID, Vec<&'a C>)> = vec![];
+		let mut sorted : Vec<&'a (EntityID, C)> = self.iter().collect();
+		sorted.sort_by_key(|&&(e, _)| e);
+		for &(entity, ref component) in sorted {
+			match entities.last_mut() {
+				Some(&mut (e, ref mut items)) if e == entity => items.push(component),
+				_ => entities.push((entity, vec![component]))
+			}
+		}
+		entities
+	}
+
+	fn has_entity(&self, entity: EntityID) -> bool {
+		self.iter().any(|&(e, _)| e == entity)
+	}
+
+	fn set_entity(&mut self, entity: EntityID, items: Vec<C>) {
+		self.retain(|&(e, _)| e != entity);
+		self.extend(items.into_iter().map(|c| (entity, c)));
+	}
+}
+
+impl EntityStorage for TagSet {
+	type Item = ();
+
+	fn entities(&self) -> Vec<(EntityID, Vec<&()>)> {
+		self.iter().map(|&e| (e, vec![&()])).collect()
+	}
+
+	fn has_entity(&self, entity: EntityID) -> bool {
+		self.contains(&entity)
+	}
+
+	fn set_entity(&mut self, entity: EntityID, items: Vec<()>) {
+		if items.is_empty() {
+			self.remove(&entity);
+		} else {
+			self.insert(entity);
+		}
+	}
+}
+
+/// Functions to encode a whole component list and to replace it with decoded data,
+/// and to do the same for the components of a single entity. Lists are decoded
+/// separately from replacing them, so callers can check all their data first.
+#[derive(Clone, Copy)]
+pub struct ListCodec {
+	pub component: TypeId,
+	pub encode: fn(&mut Vec<u8>),
+	/// Decodes a list to be passed to `replace`.
+	pub decode: DecodeAnyFn,
+	/// Replaces the list with one returned by `decode`.
+	pub replace: fn(Box<Any>),
+	/// Encodes the components of every entity in the list, sorted by entity.
+	pub encode_entities: fn() -> Vec<(EntityID, Vec<u8>)>,
+	/// Decodes the components of an entity to be passed to `set_entity`.
+	pub decode_entity: DecodeAnyFn,
+	/// Replaces the components of the entity with ones returned by `decode_entity`.
+	pub set_entity: fn(EntityID, Box<Any>),
+	/// Removes the components of the entity.
+	pub remove_entity: fn(EntityID)
 }
 
+/// Called with the entity after a component was added to it.
+pub type AddedHook = Arc<Fn(EntityID) + Send + Sync>;
+
 lazy_static! {
 	pub static ref COMPONENTS: SharedMutex<HashMap<TypeId, Component>> = SharedMutex::new(HashMap::new());
+	static ref ADDED_HOOKS: SharedMutex<HashMap<TypeId, Vec<AddedHook>>> = SharedMutex::new(HashMap::new());
+	static ref CODECS: SharedMutex<HashMap<&'static str, ListCodec>> = SharedMutex::new(HashMap::new());
+	static ref WRITTEN: SharedMutex<HashMap<TypeId, u64>> = SharedMutex::new(HashMap::new());
 }
 
 pub fn register(component : Component) {
@@ -38,61 +152,423 @@ pub fn get_components_read_lock<'mutex>(id : TypeId) -> MappedSharedMutexReadGua
 }
 
 pub fn get_components_write_lock<'mutex>(id : TypeId) -> MappedSharedMutexWriteGuard<'mutex, Any> {
+	mark_written(id);
 	let components = COMPONENTS.read().expect("COMPONENTS lock corrupted");
 	let component = components.get(&id).expect("Unknown component type requested");
 	component.getters.write_as_any()
 }
 
+/// The read and write guards returned by `get_components_locks`.
+pub type ComponentLocks<'mutex> = (Vec<MappedSharedMutexReadGuard<'mutex, Any>>, Vec<MappedSharedMutexWriteGuard<'mutex, Any>>);
+
+/// Obtains the locks on the given component lists. The locks are always acquired in
+/// the same order, sorted by type, so handlers that lock the same lists in a different
+/// order can not deadlock each other. The guards are returned in the order the types
+/// were given. Every type can only be given once, see `check_component_types`.
+pub fn get_components_locks<'mutex>(types: &[TypeId], mut_types: &[TypeId]) -> ComponentLocks<'mutex> {
+	if let Err(typ) = check_component_types(types, mut_types) {
+		panic!("Component {:?} can not be locked more than once", typ);
+	}
+
+	let mut order : Vec<(TypeId, bool, usize)> = types.iter().enumerate().map(|(i, &t)| (t, false, i))
+		.chain(mut_types.iter().enumerate().map(|(i, &t)| (t, true, i)))
+		.collect();
+	order.sort_by_key(|&(t, _, _)| t);
+
+	let mut locks : Vec<Option<MappedSharedMutexReadGuard<Any>>> = types.iter().map(|_| None).collect();
+	let mut mut_locks : Vec<Option<MappedSharedMutexWriteGuard<Any>>> = mut_types.iter().map(|_| None).collect();
+
+	for (typ, is_mut, index) in order {
+		if is_mut {
+			mut_locks[index] = Some(get_components_write_lock(typ));
+		} else {
+			locks[index] = Some(get_components_read_lock(typ));
+		}
+	}
+
+	(
+		locks.into_iter().map(|l| l.expect("Component lock was not obtained")).collect(),
+		mut_locks.into_iter().map(|l| l.expect("Component lock was not obtained")).collect()
+	)
+}
+
+/// Removes the components of the entity from every registered list. The lists are
+/// locked one at a time, so this can not be called while holding a list lock.
+pub fn remove_entity(entity: EntityID) {
+	let removers : Vec<fn(EntityID)> = {
+		let components = COMPONENTS.read().expect("COMPONENTS lock corrupted");
+		components.values().map(|c| c.remove).collect()
+	};
+
+	for remove in removers {
+		remove(entity);
+	}
+}
+
+/// Registers the codec of a component list, identified by its label.
+pub fn register_codec<L>() where L: ListType, L::Storage: Codec, <L::Storage as EntityStorage>::Item: Codec {
+	register_encoding::<L, CodecEncoding>();
+}
+
+/// Registers the codec of a component list of serde types, identified by its label.
+#[cfg(feature = "serde")]
+pub fn register_serde<L>() where L: ListType, L::Storage: Serialize + DeserializeOwned, <L::Storage as EntityStorage>::Item: Serialize + DeserializeOwned {
+	register_encoding::<L, SerdeEncoding>();
+}
+
+fn register_encoding<L, F>() where L: ListType, F: Encoding<L::Storage> + Encoding<<L::Storage as EntityStorage>::Item> {
+	let codec = ListCodec {
+		component: TypeId::of::<L::Component>(),
+		encode: encode_list::<L, F>,
+		decode: decode_list::<L, F>,
+		replace: replace_list::<L>,
+		encode_entities: encode_entities::<L, F>,
+		decode_entity: decode_entity::<L, F>,
+		set_entity: set_entity::<L>,
+		remove_entity: remove_from_list::<L>
+	};
+
+	let mut codecs = CODECS.write().expect("CODECS lock corrupted");
+	codecs.insert(L::label(), codec);
+}
+
+pub fn codec(label: &str) -> Option<ListCodec> {
+	let codecs = CODECS.read().expect("CODECS lock corrupted");
+	codecs.get(label).cloned()
+}
+
+/// The labels of the lists that have a codec, in alphabetical order.
+pub fn codec_labels() -> Vec<&'static str> {
+	let codecs = CODECS.read().expect("CODECS lock corrupted");
+	let mut labels : Vec<&'static str> = codecs.keys().cloned().collect();
+	labels.sort();
+	labels
+}
+
+/// The labels of all registered components, in alphabetical order.
+pub fn registered_labels() -> Vec<&'static str> {
+	let components = COMPONENTS.read().expect("COMPONENTS lock corrupted");
+	let mut labels : Vec<&'static str> = components.values().map(|c| c.label).collect();
+	labels.sort();
+	labels
+}
+
+fn encode_list<L, F>(out: &mut Vec<u8>) where L: ListType, F: Encoding<L::Storage> {
+	<F as Encoding<L::Storage>>::encode(&L::list().read().expect("COMPONENT_LIST corrupted"), out);
+}
+
+fn decode_list<L, F>(input: &mut &[u8]) -> Result<Box<Any>, DecodeError> where L: ListType, F: Encoding<L::Storage> {
+	let list = <F as Encoding<L::Storage>>::decode(input)?;
+	Ok(Box::new(list))
+}
+
+fn replace_list<L: ListType>(list: Box<Any>) {
+	let list = list.downcast::<L::Storage>().expect("Component list not of expected type.");
+	mark_written(TypeId::of::<L::Component>());
+	*L::list().write().expect("COMPONENT_LIST corrupted") = *list;
+}
+
+// The items of an entity are written like a Vec of items, with their count first.
+fn encode_entities<L, F>() -> Vec<(EntityID, Vec<u8>)> where L: ListType, F: Encoding<<L::Storage as EntityStorage>::Item> {
+	let list = L::list().read().expect("COMPONENT_LIST corrupted");
+	list.entities().into_iter().map(|(entity, items)| {
+		let mut out = vec![];
+		items.len().encode(&mut out);
+		for item in items {
+			F::encode(item, &mut out);
+		}
+		(entity, out)
+	}).collect()
+}
+
+fn decode_entity<L, F>(input: &mut &[u8]) -> Result<Box<Any>, DecodeError> where L: ListType, F: Encoding<<L::Storage as EntityStorage>::Item> {
+	let len = usize::decode(input)?;
+	let mut items : Vec<<L::Storage as EntityStorage>::Item> = Vec::with_capacity(len.min(input.len()));
+	for _ in 0..len {
+		items.push(F::decode(input)?);
+	}
+	Ok(Box::new(items))
+}
+
+fn set_entity<L: ListType>(entity: EntityID, items: Box<Any>) {
+	let items = items.downcast::<Vec<<L::Storage as EntityStorage>::Item>>().expect("Entity components not of expected type.");
+	mark_written(TypeId::of::<L::Component>());
+	L::list().write().expect("COMPONENT_LIST corrupted").set_entity(entity, *items);
+}
+
+fn remove_from_list<L: ListType>(entity: EntityID) {
+	let mut list = L::list().write().expect("COMPONENT_LIST corrupted");
+	if list.has_entity(entity) {
+		mark_written(TypeId::of::<L::Component>());
+		list.set_entity(entity, vec![]);
+	}
+}
+
+/// Records that the list of the component type is written in the current tick, so
+/// delta trackers encode it again. The functions of component modules and the locks
+/// of handlers do this, code that writes a `LIST` directly calls it itself or locks
+/// the list with the module's `write` function.
+pub fn mark_written(typ: TypeId) {
+	let mut written = WRITTEN.write().expect("WRITTEN lock corrupted");
+	written.insert(typ, events::current_tick());
+}
+
+/// Whether the list of the component type was written in or after the given tick.
+pub fn written_since(typ: TypeId, tick: u64) -> bool {
+	let written = WRITTEN.read().expect("WRITTEN lock corrupted");
+	written.get(&typ).is_some_and(|&written| written >= tick)
+}
+
+/// The labels of the lists with a codec that were written in or after the given tick,
+/// in alphabetical order.
+pub fn changed_since(tick: u64) -> Vec<&'static str> {
+	let codecs = CODECS.read().expect("CODECS lock corrupted");
+	let mut labels : Vec<&'static str> = codecs.iter()
+		.filter(|&(_, codec)| written_since(codec.component, tick))
+		.map(|(&label, _)| label)
+		.collect();
+	labels.sort();
+	labels
+}
+
+/// Adds a function that is called whenever a component of the given type is added to
+/// an entity.
+pub fn add_added_hook<F>(typ: TypeId, hook: F) where F: Fn(EntityID) + Send + Sync + 'static {
+	let mut hooks = ADDED_HOOKS.write().expect("ADDED_HOOKS lock corrupted");
+	hooks.entry(typ).or_default().push(Arc::new(hook));
+}
+
+/// Calls the added hooks of the component type, the hooks are free to add components.
+pub fn run_added_hooks(typ: TypeId, entity: EntityID) {
+	let hooks : Vec<AddedHook> = {
+		let hooks = ADDED_HOOKS.read().expect("ADDED_HOOKS lock corrupted");
+		match hooks.get(&typ) {
+			Some(hooks) => hooks.clone(),
+			None => return
+		}
+	};
+
+	for hook in hooks {
+		hook(entity);
+	}
+}
+
+/// Checks that no component is locked more than once, which could deadlock. Reading
+/// twice deadlocks too when a writer starts waiting between the two locks. Returns the
+/// type of the offending component.
+pub fn check_component_types(types: &[TypeId], mut_types: &[TypeId]) -> Result<(), TypeId> {
+	let all : Vec<&TypeId> = types.iter().chain(mut_types.iter()).collect();
+	for (i, typ) in all.iter().enumerate() {
+		if all[i + 1..].contains(typ) {
+			return Err(**typ);
+		}
+	}
+	Ok(())
+}
+
+/// Defines a component module with its `Component` struct and list. Attributes can be
+/// put on the struct and its fields. The struct derives `Default`, which prefabs need,
+/// unless the attributes contain a `#[derive]`, that list then has to name `Default`
+/// itself when the component is used with `Prefab::with_default`:
+///
+/// component!{ #[derive(Debug, Clone, Copy, Default)] velocity, x: f32, y: f32 }
+/// component!{ #[doc="The name shown above a unit."] label, #[doc="Kept short to fit."] text: String }
+///
+/// Existing types, like tuple structs, enums or instantiations of generic types, are
+/// registered by naming the type instead of listing fields. The type is named from
+/// within the component module:
+///
+/// component!{ health: super::Health<u32> }
+///
+/// Doc attributes document the component module, other attributes belong on the type.
+///
+/// With the `serde` feature a `#[serde]` attribute derives `Serialize` and `Deserialize`
+/// for the struct, see the `serialization` module. Named types have to derive them
+/// themselves.
 #[macro_export]
 macro_rules! component {
-	( $component_name:ident , $( $name:ident : $field:ty ),* ) => (
+	( @attributes [$(#[doc $($doc:tt)*])*] [] [$($default:tt)*] $component_name:ident : $typ:ty ) => (
+		$(#[doc $($doc)*])*
 		pub mod $component_name {
-			use shared_mutex::{ SharedMutex, MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
-			use entity_rust::entities::{ ComponentList, EntityID };
-			use entity_rust::components;
-			use std::any::{ Any, TypeId };
+			pub type Component = $typ;
 
+			$crate::component_storage!{}
+		}
+	);
+
+	( @attributes [$($attr:tt)*] [] [$($default:tt)*] $component_name:ident : $typ:ty ) => (
+		compile_error!("only doc attributes can be put on a component that names its type, they document its module, put other attributes on the type instead");
+	);
+
+	( @attributes [$($attr:tt)*] [$($item:tt)+] [$($default:tt)*] $component_name:ident : $typ:ty ) => (
+		compile_error!("#[serde] can not be used on a component that names its type, derive the serde traits on the type instead");
+	);
+
+	( @attributes [$($attr:tt)*] [$($item:tt)*] [$($default:tt)*] $component_name:ident , $( $(#[$field_attr:meta])* $name:ident : $field:ty ),* ) => (
+		pub mod $component_name {
+			$($item)*
 
-			#[derive(Default)]
+			$($default)*
+			$($attr)*
 			pub struct Component {
-				$(pub $name : $field),*
+				$( $(#[$field_attr])* pub $name : $field ),*
 			}
 
-			pub struct ListGetters;
+			$crate::component_storage!{}
 
-			impl components::MappedSharedMutexGetters for ListGetters {
-				fn read_as_any<'mutex>(&self) -> MappedSharedMutexReadGuard<'mutex, Any> {
-					let list = LIST.read().expect("COMPONENT_LIST corrupted");
-					list.into_mapped().map(|v| v as &Any)
-				}
-				fn write_as_any<'mutex>(&self) -> MappedSharedMutexWriteGuard<'mutex, Any> {
-					let list = LIST.write().expect("COMPONENT_LIST corrupted");
-					list.into_mapped().map(|v| v as &mut Any)
-				}
+			pub fn add_with_lock(mut list: MappedSharedMutexWriteGuard<ComponentList<Component>>, entity: EntityID, $($name : $field),*) {
+				let c = Component { $($name : $name),* };
+				list.push((entity,c));
 			}
+		}
+	);
 
-			lazy_static! {
-				pub static ref LIST: SharedMutex<ComponentList<Component>> = SharedMutex::new(Vec::new());
-			}
+	( $($body:tt)* ) => (
+		$crate::struct_attributes!{ component [] [] [#[derive(Default)]] $($body)* }
+	)
+}
 
-			pub fn add(entity: EntityID, component: Component) {
-				let mut list = LIST.write().expect("COMPONENT_LIST corrupted");
+/// Generates the list, registration and accessors of a component module. It expects a
+/// `Component` type to be in scope, it is used by `component!` and `#[derive(Component)]`.
+/// Handlers lock the list as the module's `Storage`, the `@list` form leaves defining
+/// `Storage` and the `add` and `remove` functions to the caller, like `tag!` does.
+#[macro_export]
+macro_rules! component_storage {
+	() => (
+		pub type Storage = $crate::entities::ComponentList<Component>;
+
+		$crate::component_storage!{ @list }
+
+		pub fn add(entity: EntityID, component: Component) {
+			{
+				let mut list = write();
 				list.push((entity, component));
 			}
+			components::run_added_hooks(TypeId::of::<Component>(), entity);
+		}
 
-			pub fn add_with_lock(mut list: MappedSharedMutexWriteGuard<ComponentList<Component>>, entity: EntityID, $($name : $field),*) {
-				let c = Component { $($name : $name),* };
-				list.push((entity,c));
+		/// Removes the components of the entity, returns whether it had any.
+		pub fn remove(entity: EntityID) -> bool {
+			let mut list = LIST.write().expect("COMPONENT_LIST corrupted");
+			let before = list.len();
+			list.retain(|&(e, _)| e != entity);
+			let removed = list.len() != before;
+			if removed {
+				components::mark_written(TypeId::of::<Component>());
+			}
+			removed
+		}
+	);
+
+	( @list ) => (
+		use $crate::shared_mutex::{ SharedMutex, SharedMutexWriteGuard, MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
+		#[allow(unused_imports)]
+		use $crate::entities::{ ComponentList, EntityID };
+		use $crate::components;
+		use std::any::{ Any, TypeId };
+
+		pub struct ListGetters;
+
+		impl components::MappedSharedMutexGetters for ListGetters {
+			fn read_as_any<'mutex>(&self) -> MappedSharedMutexReadGuard<'mutex, Any> {
+				let list = LIST.read().expect("COMPONENT_LIST corrupted");
+				list.into_mapped().map(|v| v as &Any)
+			}
+			fn write_as_any<'mutex>(&self) -> MappedSharedMutexWriteGuard<'mutex, Any> {
+				let list = LIST.write().expect("COMPONENT_LIST corrupted");
+				list.into_mapped().map(|v| v as &mut Any)
+			}
+		}
+
+		$crate::lazy_static::lazy_static! {
+			pub static ref LIST: SharedMutex<Storage> = SharedMutex::new(Storage::new());
+		}
+
+		/// Locks the list for writing and records the write for delta trackers.
+		pub fn write() -> SharedMutexWriteGuard<'static, Storage> {
+			components::mark_written(TypeId::of::<Component>());
+			LIST.write().expect("COMPONENT_LIST corrupted")
+		}
+
+		/// Identifies the component list in snapshots.
+		pub const LABEL: &'static str = module_path!();
+
+		/// Gives generic code, like `components::register_codec`, access to the list.
+		pub struct List;
+
+		impl components::ListType for List {
+			type Component = Component;
+			type Storage = Storage;
+
+			fn label() -> &'static str { LABEL }
+			fn list() -> &'static SharedMutex<Storage> { &LIST }
+		}
+
+		pub fn register() {
+			let type_id = TypeId::of::<Component>();
+			let component_entry = components::Component {
+				name : type_id,
+				label : LABEL,
+				getters : Box::new(ListGetters),
+				remove : remove_entity
+			};
+
+			components::register(component_entry);
+		}
+
+		fn remove_entity(entity: EntityID) {
+			remove(entity);
+		}
+
+		/// Adds a function that is called with every entity this component is added to.
+		pub fn on_added<F>(hook: F) where F: Fn(EntityID) + Send + Sync + 'static {
+			components::add_added_hook(TypeId::of::<Component>(), hook);
+		}
+	)
+}
+
+/// Defines a tag, a component without data. Only the set of tagged entities is stored,
+/// handlers use it as a filter:
+///
+/// tag!{ player }
+///
+/// on turn, {}, { players: player }, (self, data) => {
+///   if players.contains(&data[0].entity) { .. }
+/// }
+#[macro_export]
+macro_rules! tag {
+	( $(#[$attr:meta])* $tag_name:ident ) => (
+		$(#[$attr])*
+		pub mod $tag_name {
+			/// Identifies the tag in the components registry, no instances are stored.
+			pub struct Component;
+
+			pub type Storage = $crate::entities::TagSet;
+
+			$crate::component_storage!{ @list }
+
+			/// Tags the entity, returns whether it was not tagged yet.
+			pub fn add(entity: EntityID) -> bool {
+				let added = write().insert(entity);
+				if added {
+					components::run_added_hooks(TypeId::of::<Component>(), entity);
+				}
+				added
 			}
 
-			pub fn register() {
-				let type_id = TypeId::of::<Component>();
-				let component_entry = components::Component {
-					name : type_id,
-					getters : Box::new(ListGetters)
-				};
+			/// Untags the entity, returns whether it was tagged.
+			pub fn remove(entity: EntityID) -> bool {
+				let mut list = LIST.write().expect("COMPONENT_LIST corrupted");
+				let removed = list.remove(&entity);
+				if removed {
+					components::mark_written(TypeId::of::<Component>());
+				}
+				removed
+			}
 
-				components::register(component_entry);
+			pub fn contains(entity: EntityID) -> bool {
+				let list = LIST.read().expect("COMPONENT_LIST corrupted");
+				list.contains(&entity)
 			}
 		}
 	)
@@ -0,0 +1,253 @@
+/// Deltas carry the changes to the component lists between two ticks, so a world can
+/// be autosaved or mirrored without encoding all of it every tick. Lists record the tick
+/// they are written in, see `components::changed_since`. A tracker encodes only the
+/// lists written since its last delta, and compares the components of every entity
+/// with a hash of their encoding as of its last delta to find what was added, changed
+/// or removed since then:
+///
+/// let mut tracker = Tracker::new();
+/// // .. run some ticks
+/// let bytes = codec::to_bytes(&tracker.delta());
+///
+/// // on the other world, after applying the earlier deltas
+/// codec::from_bytes::<Delta>(&bytes)?.apply()?;
+///
+/// Only the lists that have a codec registered with `components::register_codec` are
+/// tracked. Lists written without their module's functions are only seen after
+/// `components::mark_written`. An entity is spawned when it gets its first tracked component and is
+/// despawned when it loses its last one, applying a delta despawns it with
+/// `entities::despawn` so its despawn hooks run.
+use std::collections::{ BTreeMap, BTreeSet };
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hasher;
+
+use codec::DecodeError;
+use components;
+use entities::{ self, EntityID };
+use events;
+
+/// The changes to a single component list, components are encoded per entity.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListDelta {
+	pub label: String,
+	pub added: Vec<(EntityID, Vec<u8>)>,
+	pub changed: Vec<(EntityID, Vec<u8>)>,
+	pub removed: Vec<EntityID>
+}
+
+codec!{ ListDelta { label, added, changed, removed } }
+
+impl ListDelta {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+	}
+}
+
+/// The changes to the world between two ticks.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Delta {
+	pub from_tick: u64,
+	pub to_tick: u64,
+	/// The identifier the allocator hands out next, applying the delta makes sure the
+	/// other world does not hand out the identifiers of spawned entities.
+	pub next_entity: EntityID,
+	pub spawned: Vec<EntityID>,
+	pub despawned: Vec<EntityID>,
+	pub lists: Vec<ListDelta>
+}
+
+codec!{ Delta { from_tick, to_tick, next_entity, spawned, despawned, lists } }
+
+#[derive(Debug)]
+pub enum DeltaError {
+	UnknownComponent(String),
+	Decode(String, DecodeError)
+}
+
+impl fmt::Display for DeltaError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			DeltaError::UnknownComponent(ref label) => write!(f, "Changed component {} has no codec registered", label),
+			DeltaError::Decode(ref label, ref error) => write!(f, "Could not decode changed {}: {}", label, error)
+		}
+	}
+}
+
+impl Error for DeltaError {}
+
+impl Delta {
+	pub fn is_empty(&self) -> bool {
+		self.spawned.is_empty() && self.despawned.is_empty() && self.lists.is_empty()
+	}
+
+	/// Applies the changes to this world. Deltas have to be applied in the order they
+	/// were made, starting from the world the tracker started from. Every component is
+	/// decoded before anything is changed, so nothing changes when applying fails. The
+	/// added hooks of added components run after all lists have been changed.
+	pub fn apply(&self) -> Result<(), DeltaError> {
+		let mut lists = vec![];
+		for list in self.lists.iter() {
+			let list_codec = components::codec(&list.label).ok_or_else(|| DeltaError::UnknownComponent(list.label.clone()))?;
+			let mut items = vec![];
+			for &(entity, ref data) in list.added.iter().chain(list.changed.iter()) {
+				let decoded = (list_codec.decode_entity)(&mut &data[..]).map_err(|e| DeltaError::Decode(list.label.clone(), e))?;
+				items.push((entity, decoded));
+			}
+			lists.push((list, list_codec, items));
+		}
+
+		for &entity in self.despawned.iter() {
+			entities::despawn(entity);
+		}
+
+		for &mut (list, ref list_codec, ref mut items) in lists.iter_mut() {
+			for (entity, decoded) in items.drain(..) {
+				(list_codec.set_entity)(entity, decoded);
+			}
+			for &entity in list.removed.iter() {
+				(list_codec.remove_entity)(entity);
+			}
+		}
+
+		if entities::next_entity_id() < self.next_entity {
+			entities::set_next_entity_id(self.next_entity);
+		}
+
+		for &(list, ref list_codec, _) in lists.iter() {
+			for &(entity, _) in list.added.iter() {
+				components::run_added_hooks(list_codec.component, entity);
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Remembers hashes of the encoded components of the tracked lists to make deltas, see
+/// the module documentation.
+pub struct Tracker {
+	tick: u64,
+	only: Option<BTreeSet<String>>,
+	lists: BTreeMap<String, BTreeMap<EntityID, u64>>,
+	/// The number of tracked lists every entity has components in.
+	entities: BTreeMap<EntityID, usize>
+}
+
+// A changed component is only missed when its encoding has the same 64 bit hash.
+fn hash(data: &[u8]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	hasher.write(data);
+	hasher.finish()
+}
+
+impl Default for Tracker {
+	fn default() -> Tracker {
+		Tracker::new()
+	}
+}
+
+impl Tracker {
+	/// Tracks changes from an empty world, so the first delta contains every component.
+	pub fn new() -> Tracker {
+		Tracker { tick: 0, only: None, lists: BTreeMap::new(), entities: BTreeMap::new() }
+	}
+
+	/// Tracks changes to the lists with the given labels only, from an empty world.
+	pub fn for_lists(labels: &[&str]) -> Tracker {
+		Tracker {
+			tick: 0,
+			only: Some(labels.iter().map(|l| l.to_string()).collect()),
+			lists: BTreeMap::new(),
+			entities: BTreeMap::new()
+		}
+	}
+
+	/// Tracks changes from the current state of the world.
+	pub fn from_now() -> Tracker {
+		let mut tracker = Tracker::new();
+		tracker.delta();
+		tracker
+	}
+
+	/// The tick of the last delta, the next delta contains the changes since then.
+	pub fn tick(&self) -> u64 {
+		self.tick
+	}
+
+	/// Collects the changes since the last delta. Handlers should not be running, the
+	/// lists are locked one at a time.
+	pub fn delta(&mut self) -> Delta {
+		let mut lists = vec![];
+		// the list counts of the entities that gained or lost components, before the delta
+		let mut touched : BTreeMap<EntityID, usize> = BTreeMap::new();
+
+		for label in components::codec_labels() {
+			if let Some(ref only) = self.only {
+				if !only.contains(label) {
+					continue;
+				}
+			}
+
+			let list_codec = components::codec(label).expect("Component codec was removed");
+			if self.lists.contains_key(label) && !components::written_since(list_codec.component, self.tick) {
+				continue;
+			}
+
+			let previous = self.lists.remove(label).unwrap_or_default();
+			let mut current = BTreeMap::new();
+			let mut list = ListDelta { label: label.to_string(), ..ListDelta::default() };
+			for (entity, data) in (list_codec.encode_entities)() {
+				let data_hash = hash(&data);
+				match previous.get(&entity) {
+					None => list.added.push((entity, data)),
+					Some(&old) if old != data_hash => list.changed.push((entity, data)),
+					Some(_) => ()
+				}
+				current.insert(entity, data_hash);
+			}
+			list.removed = previous.keys().filter(|e| !current.contains_key(e)).cloned().collect();
+
+			for &(entity, _) in list.added.iter() {
+				let count = self.entities.entry(entity).or_insert(0);
+				touched.entry(entity).or_insert(*count);
+				*count += 1;
+			}
+			for &entity in list.removed.iter() {
+				let count = self.entities.get_mut(&entity).expect("Removed entity was not counted");
+				touched.entry(entity).or_insert(*count);
+				*count -= 1;
+			}
+
+			if !list.is_empty() {
+				lists.push(list);
+			}
+			self.lists.insert(label.to_string(), current);
+		}
+
+		let mut spawned = vec![];
+		let mut despawned = vec![];
+		for (entity, before) in touched {
+			let after = self.entities[&entity];
+			if before == 0 && after > 0 {
+				spawned.push(entity);
+			} else if before > 0 && after == 0 {
+				despawned.push(entity);
+			}
+			if after == 0 {
+				self.entities.remove(&entity);
+			}
+		}
+
+		let delta = Delta {
+			from_tick: self.tick,
+			to_tick: events::current_tick(),
+			next_entity: entities::next_entity_id(),
+			spawned: spawned,
+			despawned: despawned,
+			lists: lists
+		};
+		self.tick = delta.to_tick;
+		delta
+	}
+}
@@ -1,2 +1,139 @@
+/// Entities are identifiers that components are associated with. New identifiers
+/// are handed out by `allocate`, an entity with many components is best created with
+/// an `EntityBuilder`:
+///
+/// let unit = EntityBuilder::new()
+///   .with(position::Component { x: 0, y: 0 })
+///   .with(health::Component { hp: 10 })
+///   .tag::<player::Component>()
+///   .spawn();
+///
+/// The components are added while holding the locks of all their lists at once, so
+/// handlers never see the entity with only some of its components. The added hooks of
+/// the components run after that.
+///
+/// `despawn` removes an entity from every registered component list, after running
+/// the despawn hooks. It locks the lists itself, so handlers can not call it.
+///
+use std::any::{ Any, TypeId };
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use shared_mutex::SharedMutex;
+
+use components;
+
 pub type EntityID = usize;
 pub type ComponentList<T> = Vec<(EntityID,T)>;
+/// The entities that have a tag, kept sorted so they iterate in a stable order.
+pub type TagSet = BTreeSet<EntityID>;
+
+/// Called with an entity that is about to be despawned, it still has its components.
+pub type DespawnHook = Arc<Fn(EntityID) + Send + Sync>;
+
+lazy_static! {
+	static ref DESPAWN_HOOKS: SharedMutex<Vec<DespawnHook>> = SharedMutex::new(vec![]);
+}
+
+static NEXT_ENTITY_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Hands out a new entity identifier.
+pub fn allocate() -> EntityID {
+	NEXT_ENTITY_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+/// The identifier `allocate` hands out next.
+pub fn next_entity_id() -> EntityID {
+	NEXT_ENTITY_ID.load(Ordering::SeqCst)
+}
+
+/// Sets the identifier `allocate` hands out next, used when restoring a snapshot.
+pub fn set_next_entity_id(entity: EntityID) {
+	NEXT_ENTITY_ID.store(entity, Ordering::SeqCst);
+}
+
+/// Adds a function that is called for every despawned entity.
+pub fn on_despawn<F>(hook: F) where F: Fn(EntityID) + Send + Sync + 'static {
+	let mut hooks = DESPAWN_HOOKS.write().expect("DESPAWN_HOOKS lock corrupted");
+	hooks.push(Arc::new(hook));
+}
+
+/// Runs the despawn hooks and removes the entity from every component list.
+pub fn despawn(entity: EntityID) {
+	let hooks : Vec<DespawnHook> = DESPAWN_HOOKS.read().expect("DESPAWN_HOOKS lock corrupted").clone();
+	for hook in hooks {
+		hook(entity);
+	}
+
+	components::remove_entity(entity);
+}
+
+type Insert = Box<FnOnce(&mut Any, EntityID)>;
+
+/// Collects the components of a new entity, see the module documentation.
+pub struct EntityBuilder {
+	entity: EntityID,
+	inserts: Vec<(TypeId, Insert)>
+}
+
+impl Default for EntityBuilder {
+	fn default() -> EntityBuilder {
+		EntityBuilder::new()
+	}
+}
+
+impl EntityBuilder {
+	pub fn new() -> EntityBuilder {
+		EntityBuilder::for_entity(allocate())
+	}
+
+	/// Builds an entity with an identifier that was allocated before.
+	pub fn for_entity(entity: EntityID) -> EntityBuilder {
+		EntityBuilder { entity: entity, inserts: vec![] }
+	}
+
+	pub fn entity(&self) -> EntityID {
+		self.entity
+	}
+
+	/// Adds a component, the component module has to be registered. Adding a component
+	/// of the same type again replaces it.
+	pub fn with<C: Any>(self, component: C) -> EntityBuilder {
+		self.insert(TypeId::of::<C>(), Box::new(move |list: &mut Any, entity| {
+			let list = list.downcast_mut::<ComponentList<C>>().expect("Component list not of expected type.");
+			list.push((entity, component));
+		}))
+	}
+
+	/// Adds the tag of which `T` is the `Component` type, the tag has to be registered.
+	pub fn tag<T: Any>(self) -> EntityBuilder {
+		self.insert(TypeId::of::<T>(), Box::new(|list: &mut Any, entity| {
+			let list = list.downcast_mut::<TagSet>().expect("Tag set not of expected type.");
+			list.insert(entity);
+		}))
+	}
+
+	fn insert(mut self, typ: TypeId, insert: Insert) -> EntityBuilder {
+		self.inserts.retain(|&(t, _)| t != typ);
+		self.inserts.push((typ, insert));
+		self
+	}
+
+	/// Adds all components to their lists and runs their added hooks.
+	pub fn spawn(self) -> EntityID {
+		let entity = self.entity;
+		let types : Vec<TypeId> = self.inserts.iter().map(|&(t, _)| t).collect();
+
+		{
+			let (_, mut lists) = components::get_components_locks(&[], &types);
+			for ((_, insert), list) in self.inserts.into_iter().zip(lists.iter_mut()) {
+				insert(&mut **list, entity);
+			}
+		}
+
+		for typ in types {
+			components::run_added_hooks(typ, entity);
+		}
+		entity
+	}
+}
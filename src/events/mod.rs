@@ -17,45 +17,421 @@
 ///
 use std::collections::{ HashSet, HashMap };
 use std::any::{ Any, TypeId };
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{ AtomicBool, AtomicU64, AtomicUsize, Ordering };
+use std::cell::Cell;
 use shared_mutex::{ SharedMutex, MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
 
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+use codec::{ Codec, CodecEncoding, DecodeAnyFn, DecodeError, Encoding };
 use components;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, de::DeserializeOwned };
+#[cfg(feature = "serde")]
+use serialization::SerdeEncoding;
+#[cfg(feature = "tracing")]
+use tracing;
 
 pub trait Handler {
 	fn run(&self, Vec<MappedSharedMutexReadGuard<Any>>, Vec<MappedSharedMutexWriteGuard<Any>>);
 	fn component_types(&self) -> Vec<TypeId>;
 	fn mut_component_types(&self) -> Vec<TypeId>;
+	/// The module path of the system that registered the handler.
+	fn system_name(&self) -> &'static str;
+	/// The number of data items the handler is invoked with.
+	fn data_count(&self) -> usize;
 }
 
+/// A registered event. The name is the module path of the event module, so it is the
+/// same in every run and can be used in logs, replays and network messages.
 #[derive(Clone)]
 pub struct Event {
 	pub name: String,
+	/// The type of the event data, used to detect two events with the same name.
+	pub type_id: TypeId,
+	/// The number of data items that are queued for this tick.
+	pub data_count: fn () -> usize,
 	pub get_handler_instances: fn () -> Vec<Box<Handler>>
 }
 
+/// Implemented by the `Event` type of every event module, so services like the
+/// recorder can work with any event.
+pub trait EventType: 'static {
+	type Data: Any;
+
+	fn name() -> &'static str;
+	fn trigger_data_after(ticks: u64, data: Self::Data);
+	/// Queues the data for this tick without notifying the trigger observers, used to
+	/// deliver data that was triggered elsewhere, see `observe_delivery`.
+	fn deliver(data: Self::Data);
+	fn register();
+	fn advance_tick() -> bool;
+	fn this_tick_data() -> &'static SharedMutex<Vec<Self::Data>>;
+	fn next_tick_data() -> &'static SharedMutex<Vec<(u64, Self::Data)>>;
+}
+
+/// A single call to one of the trigger functions of an event. External triggers
+/// come from outside of the simulation, like user input, triggers from event
+/// handlers and timers are internal.
+pub struct Trigger<'a> {
+	pub event_name: &'a str,
+	pub tick: u64,
+	pub delay: u64,
+	pub data: &'a Any,
+	pub external: bool
+}
+
+pub type ObserverID = usize;
+
+pub type ObserverFn = Box<Fn(&Trigger) + Send + Sync>;
+
+/// Functions to encode the data of an event and to trigger it from encoded data.
+#[derive(Clone, Copy)]
+pub struct EventCodec {
+	pub encode: fn(&Any, &mut Vec<u8>),
+	pub trigger_after: fn(u64, &mut &[u8]) -> Result<(), DecodeError>,
+	/// Decodes data and delivers it for this tick, see `EventType::deliver`. The observers
+	/// are notified with `observe_delivery`.
+	pub deliver: fn(&mut &[u8]) -> Result<(), DecodeError>,
+	/// Encodes the data that is queued for this tick and later ticks.
+	pub save_queues: fn(&mut Vec<u8>),
+	/// Decodes queued data to be passed to `restore_queues`.
+	pub decode_queues: DecodeAnyFn,
+	/// Replaces the queued data with data returned by `decode_queues` and schedules
+	/// the event.
+	pub restore_queues: fn(Box<Any>)
+}
+
+/// Decides how the data of multiple triggers of an event within a single tick
+/// is combined before it is handed to the handlers.
+pub enum Coalesce<D> {
+	/// Every trigger is kept, in the order they were triggered.
+	All,
+	/// Only the most recent trigger is kept.
+	Latest,
+	/// Only the first trigger is kept, later triggers are dropped.
+	First,
+	/// Every trigger is merged into the first one with the given function.
+	Merge(fn(&mut D, D))
+}
+
+impl<D> Coalesce<D> {
+	pub fn push(&self, queue: &mut Vec<D>, data: D) {
+		match *self {
+			Coalesce::All => queue.push(data),
+			Coalesce::Latest => {
+				queue.clear();
+				queue.push(data);
+			},
+			Coalesce::First => {
+				if queue.is_empty() {
+					queue.push(data);
+				}
+			},
+			Coalesce::Merge(merge) => {
+				if queue.is_empty() {
+					queue.push(data);
+				} else {
+					merge(&mut queue[0], data);
+				}
+			}
+		}
+	}
+}
+
+/// Moves the data of an event that is due this tick into its this tick queue, returns
+/// whether the event still has data queued for later ticks.
+pub type AdvanceFn = fn() -> bool;
+
 // The new events sets contain the events that have data in their queues so
-// are ready to be ran.
+// are ready to be ran. The next tick events are stored with the function that
+// advances their queues.
 lazy_static! {
 	pub static ref THIS_TICK_NEW_EVENTS: SharedMutex<HashSet<String>> = SharedMutex::new(HashSet::new());
-	pub static ref NEXT_TICK_NEW_EVENTS: SharedMutex<HashSet<String>> = SharedMutex::new(HashSet::new());
+	pub static ref NEXT_TICK_NEW_EVENTS: SharedMutex<HashMap<String, AdvanceFn>> = SharedMutex::new(HashMap::new());
 	pub static ref REGISTERED_EVENTS: SharedMutex<HashMap<String, Event>> = SharedMutex::new(HashMap::new());
+	static ref LIMITS: SharedMutex<Limits> = SharedMutex::new(Limits::default());
+	static ref OBSERVERS: SharedMutex<Vec<(ObserverID, ObserverFn)>> = SharedMutex::new(vec![]);
+	static ref CODECS: SharedMutex<HashMap<String, EventCodec>> = SharedMutex::new(HashMap::new());
+}
+
+static EVENTS_THIS_TICK: AtomicUsize = AtomicUsize::new(0);
+static CURRENT_TICK: AtomicU64 = AtomicU64::new(0);
+static NEXT_OBSERVER_ID: AtomicUsize = AtomicUsize::new(0);
+static OBSERVED: AtomicBool = AtomicBool::new(false);
+
+thread_local!(static INTERNAL_TRIGGERS: Cell<bool> = const { Cell::new(false) });
+
+/// The number of times `next_tick` has been called.
+pub fn current_tick() -> u64 {
+	CURRENT_TICK.load(Ordering::SeqCst)
+}
+
+/// Sets the tick counter, used when restoring a snapshot.
+pub fn set_current_tick(tick: u64) {
+	CURRENT_TICK.store(tick, Ordering::SeqCst);
+}
+
+/// Adds a function that is called for every trigger of every event.
+pub fn add_trigger_observer<F>(observer: F) -> ObserverID where F: Fn(&Trigger) + Send + Sync + 'static {
+	let id = NEXT_OBSERVER_ID.fetch_add(1, Ordering::SeqCst);
+	let mut observers = OBSERVERS.write().expect("OBSERVERS mutex was corrupted");
+	observers.push((id, Box::new(observer)));
+	OBSERVED.store(true, Ordering::SeqCst);
+	id
+}
+
+pub fn remove_trigger_observer(id: ObserverID) {
+	let mut observers = OBSERVERS.write().expect("OBSERVERS mutex was corrupted");
+	observers.retain(|&(observer_id, _)| observer_id != id);
+	OBSERVED.store(!observers.is_empty(), Ordering::SeqCst);
+}
+
+/// Marks the triggers on this thread as internal until it is dropped. Triggers from
+/// handlers and timers are internal, they happen again when the simulation is run
+/// so they are not recorded or forwarded.
+pub struct InternalTriggers {
+	was_internal: bool
+}
+
+impl InternalTriggers {
+	pub fn start() -> InternalTriggers {
+		let was_internal = INTERNAL_TRIGGERS.with(|internal| internal.replace(true));
+		InternalTriggers { was_internal: was_internal }
+	}
+}
+
+impl Drop for InternalTriggers {
+	fn drop(&mut self) {
+		let was_internal = self.was_internal;
+		INTERNAL_TRIGGERS.with(|internal| internal.set(was_internal));
+	}
+}
+
+/// Called by the trigger functions of the events to notify the observers.
+pub fn observe_trigger(event_name: &str, delay: u64, data: &Any) {
+	if !OBSERVED.load(Ordering::SeqCst) {
+		return;
+	}
+
+	notify_observers(&Trigger {
+		event_name: event_name,
+		tick: current_tick(),
+		delay: delay,
+		data: data,
+		external: !INTERNAL_TRIGGERS.with(|internal| internal.get())
+	});
+}
+
+/// Notifies the observers of data that was triggered elsewhere and is delivered for
+/// this tick, as an external trigger. Delivered data runs in the current tick, so it
+/// is observed as a trigger of the previous tick, which runs in the next one.
+pub fn observe_delivery(event_name: &str, data: &Any) {
+	if !OBSERVED.load(Ordering::SeqCst) {
+		return;
+	}
+
+	notify_observers(&Trigger {
+		event_name: event_name,
+		tick: current_tick().saturating_sub(1),
+		delay: 0,
+		data: data,
+		external: true
+	});
+}
+
+fn notify_observers(trigger: &Trigger) {
+	let observers = OBSERVERS.read().expect("OBSERVERS mutex was corrupted");
+	for (_, observer) in observers.iter() {
+		observer(trigger);
+	}
+}
+
+/// Registers the codec of an event so its triggers can be encoded, for example
+/// by the recorder.
+pub fn register_codec<E>() where E: EventType, E::Data: Codec {
+	register_encoding::<E, CodecEncoding>();
+}
+
+/// Registers the codec of an event with serde data.
+#[cfg(feature = "serde")]
+pub fn register_serde<E>() where E: EventType, E::Data: Serialize + DeserializeOwned {
+	register_encoding::<E, SerdeEncoding>();
+}
+
+fn register_encoding<E, F>() where E: EventType, F: QueueEncoding<E::Data> {
+	let codec = EventCodec {
+		encode: encode_data::<E::Data, F>,
+		trigger_after: decode_and_trigger::<E, F>,
+		deliver: decode_and_deliver::<E, F>,
+		save_queues: save_queues::<E, F>,
+		decode_queues: decode_queues::<E, F>,
+		restore_queues: restore_queues::<E>
+	};
+
+	let mut codecs = CODECS.write().expect("CODECS mutex was corrupted");
+	codecs.insert(E::name().to_string(), codec);
+}
+
+pub fn codec(event_name: &str) -> Option<EventCodec> {
+	let codecs = CODECS.read().expect("CODECS mutex was corrupted");
+	codecs.get(event_name).cloned()
 }
 
-pub fn trigger_this_tick(event_name: &String) {
+/// The names of the events that have a codec, in alphabetical order.
+pub fn codec_names() -> Vec<String> {
+	let codecs = CODECS.read().expect("CODECS mutex was corrupted");
+	let mut names : Vec<String> = codecs.keys().cloned().collect();
+	names.sort();
+	names
+}
+
+/// An encoding of event data and of the queues it is stored in.
+trait QueueEncoding<D>: Encoding<D> + Encoding<Vec<D>> + Encoding<Vec<(u64, D)>> {}
+
+impl<D, F> QueueEncoding<D> for F where F: Encoding<D> + Encoding<Vec<D>> + Encoding<Vec<(u64, D)>> {}
+
+fn encode_data<D: Any, F: QueueEncoding<D>>(data: &Any, out: &mut Vec<u8>) {
+	<F as Encoding<D>>::encode(data.downcast_ref::<D>().expect("Event data not of expected type."), out);
+}
+
+fn decode_and_trigger<E, F>(ticks: u64, input: &mut &[u8]) -> Result<(), DecodeError> where E: EventType, F: QueueEncoding<E::Data> {
+	let data = <F as Encoding<E::Data>>::decode(input)?;
+	E::trigger_data_after(ticks, data);
+	Ok(())
+}
+
+fn decode_and_deliver<E, F>(input: &mut &[u8]) -> Result<(), DecodeError> where E: EventType, F: QueueEncoding<E::Data> {
+	let data = <F as Encoding<E::Data>>::decode(input)?;
+	observe_delivery(E::name(), &data);
+	E::deliver(data);
+	Ok(())
+}
+
+fn save_queues<E, F>(out: &mut Vec<u8>) where E: EventType, F: QueueEncoding<E::Data> {
+	<F as Encoding<Vec<E::Data>>>::encode(&E::this_tick_data().read().expect("THIS_TICK_DATA mutex corrupted"), out);
+	<F as Encoding<Vec<(u64, E::Data)>>>::encode(&E::next_tick_data().read().expect("NEXT_TICK_DATA mutex corrupted"), out);
+}
+
+fn decode_queues<E, F>(input: &mut &[u8]) -> Result<Box<Any>, DecodeError> where E: EventType, F: QueueEncoding<E::Data> {
+	let this_tick = <F as Encoding<Vec<E::Data>>>::decode(input)?;
+	let next_tick = <F as Encoding<Vec<(u64, E::Data)>>>::decode(input)?;
+	Ok(Box::new((this_tick, next_tick)))
+}
+
+fn restore_queues<E: EventType>(queues: Box<Any>) {
+	let (this_tick, next_tick) = *queues.downcast::<(Vec<E::Data>, Vec<(u64, E::Data)>)>().expect("Event queues not of expected type.");
+
+	// the schedules may still hold the event from before the restore
+	E::register();
+	if this_tick.is_empty() {
+		THIS_TICK_NEW_EVENTS.write().expect("THIS_TICK_NEW_EVENTS mutex was corrupted.").remove(E::name());
+	} else {
+		trigger_this_tick(E::name());
+	}
+	if next_tick.is_empty() {
+		NEXT_TICK_NEW_EVENTS.write().expect("NEXT_TICK_NEW_EVENTS mutex was corrupted.").remove(E::name());
+	} else {
+		trigger_next_tick(E::name(), E::advance_tick);
+	}
+	*E::this_tick_data().write().expect("THIS_TICK_DATA mutex corrupted") = this_tick;
+	*E::next_tick_data().write().expect("NEXT_TICK_DATA mutex corrupted") = next_tick;
+}
+
+/// Limits that protect a tick against handlers that keep triggering events.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+	/// The maximum number of times `run_tick` runs the events that were triggered
+	/// by the previous run.
+	pub max_cascade_depth: usize,
+	/// The maximum number of triggers that are run within a single tick, triggers
+	/// that were coalesced into one count once.
+	pub max_events_per_tick: usize
+}
+
+impl Default for Limits {
+	fn default() -> Limits {
+		Limits {
+			max_cascade_depth: 64,
+			max_events_per_tick: 10000
+		}
+	}
+}
+
+pub fn set_limits(limits: Limits) {
+	*LIMITS.write().expect("LIMITS mutex was corrupted") = limits;
+}
+
+pub fn limits() -> Limits {
+	*LIMITS.read().expect("LIMITS mutex was corrupted")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+	CascadeDepth(usize),
+	EventsPerTick(usize)
+}
+
+/// Returned by `run_tick` when a limit was exceeded. The chain contains the names
+/// of the events that were run at each depth of the cascade.
+#[derive(Debug)]
+pub struct CascadeError {
+	pub limit: Limit,
+	pub chain: Vec<Vec<String>>
+}
+
+impl fmt::Display for CascadeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.limit {
+			Limit::CascadeDepth(depth) => write!(f, "Event cascade exceeded the maximum depth of {}", depth)?,
+			Limit::EventsPerTick(count) => write!(f, "Tick exceeded the maximum of {} triggered events", count)?
+		}
+		for (depth, event_names) in self.chain.iter().enumerate() {
+			write!(f, "\n  {}: {}", depth, event_names.join(", "))?;
+		}
+		Ok(())
+	}
+}
+
+impl Error for CascadeError {}
+
+pub fn trigger_this_tick(event_name: &str) {
 	let mut new_events_set = THIS_TICK_NEW_EVENTS.write().expect("THIS_TICK_NEW_EVENTS mutex was corrupted.");
-	new_events_set.insert(event_name.clone());
+	new_events_set.insert(event_name.to_string());
 }
 
-pub fn trigger_next_tick(event_name: &String) {
-	let mut new_events_set = NEXT_TICK_NEW_EVENTS.write().expect("NEXT_TICK_NEW_EVENTS mutex was corrupted.");
-	new_events_set.insert(event_name.clone());
+pub fn trigger_next_tick(event_name: &str, advance: AdvanceFn) {
+	let mut new_events_map = NEXT_TICK_NEW_EVENTS.write().expect("NEXT_TICK_NEW_EVENTS mutex was corrupted.");
+	new_events_map.insert(event_name.to_string(), advance);
 }
 
 pub fn register_event(event : Event) {
 	let mut events = REGISTERED_EVENTS.write().expect("REGISTERED_EVENTS mutex was corrupted.");
+	if let Some(existing) = events.get(&event.name) {
+		if existing.type_id != event.type_id {
+			panic!("Two different events are registered with the name {}", event.name);
+		}
+	}
 	events.insert(event.name.clone(), event);
 }
 
+/// Looks up a registered event by its name.
+pub fn lookup(event_name: &str) -> Option<Event> {
+	let events = REGISTERED_EVENTS.read().expect("REGISTERED_EVENTS mutex was corrupted.");
+	events.get(event_name).cloned()
+}
+
+/// The names of all registered events, in alphabetical order.
+pub fn registered_event_names() -> Vec<String> {
+	let events = REGISTERED_EVENTS.read().expect("REGISTERED_EVENTS mutex was corrupted.");
+	let mut names : Vec<String> = events.keys().cloned().collect();
+	names.sort();
+	names
+}
+
 pub fn has_events() -> bool {
 	THIS_TICK_NEW_EVENTS.read().expect("THIS_TICK_NEW_EVENTS mutex was corrupted").len() > 0
 }
@@ -63,9 +439,53 @@ pub fn has_events() -> bool {
 // Runs a single iteration of the event system. Can be run in a loop to process events
 // continuously, but should be interleaved with `next_tick` to progress properly.
 pub fn run_events() {
+	run_event_batch();
+}
+
+// Runs the events of this tick until no new events are triggered, failing when one of
+// the configured limits is exceeded. The events that were not run yet stay queued.
+pub fn run_tick() -> Result<(), CascadeError> {
+	#[cfg(feature = "tracing")]
+	let tick_start = Instant::now();
+
+	let result = run_cascade();
+
+	#[cfg(feature = "tracing")]
+	tracing::tick(&tracing::TickSpan {
+		tick: current_tick(),
+		events: EVENTS_THIS_TICK.load(Ordering::SeqCst),
+		duration: tick_start.elapsed()
+	});
+
+	result
+}
+
+fn run_cascade() -> Result<(), CascadeError> {
+	let limits = limits();
+	let mut chain : Vec<Vec<String>> = vec![];
+
+	while has_events() {
+		if chain.len() >= limits.max_cascade_depth {
+			return Err(CascadeError { limit: Limit::CascadeDepth(limits.max_cascade_depth), chain: chain });
+		}
+
+		let event_names = run_event_batch();
+		let count = EVENTS_THIS_TICK.load(Ordering::SeqCst);
+		chain.push(event_names);
+
+		if count > limits.max_events_per_tick {
+			return Err(CascadeError { limit: Limit::EventsPerTick(limits.max_events_per_tick), chain: chain });
+		}
+	}
+
+	Ok(())
+}
+
+// Runs the events that are queued this tick and returns their names.
+fn run_event_batch() -> Vec<String> {
 	let events : Vec<Event>;
+	let event_names : Vec<String>;
 	{
-		let event_names : Vec<String>;
 		{
 			let mut event_names_lock = THIS_TICK_NEW_EVENTS.write().expect("THIS_TICK_NEW_EVENTS mutex was corrupted");
 			event_names = event_names_lock.drain().collect();
@@ -77,27 +497,67 @@ pub fn run_events() {
 		).collect();
 	}
 
-	let handlers = events.iter().flat_map(|e| (e.get_handler_instances)() );
+	let triggered : usize = events.iter().map(|event| (event.data_count)()).sum();
+	EVENTS_THIS_TICK.fetch_add(triggered, Ordering::SeqCst);
+
+	for event in events.iter() {
+		run_event(event);
+	}
+
+	event_names
+}
+
+// Runs the handlers of a single event with the data that was queued for it.
+fn run_event(event: &Event) {
+	#[cfg(feature = "tracing")]
+	let batch_start = Instant::now();
+
+	let handlers = (event.get_handler_instances)();
+	#[cfg(feature = "tracing")]
+	let handler_count = handlers.len();
 
 	// TODO sort and parellize handler invocations
 	for handler in handlers {
+		#[cfg(feature = "tracing")]
+		let lock_start = Instant::now();
+		#[cfg(feature = "tracing")]
+		let data_count = handler.data_count();
+		#[cfg(feature = "tracing")]
+		let system_name = handler.system_name();
+
 		let component_types = handler.component_types();
 		let mut_component_types = handler.mut_component_types();
 
-		let mut locks : Vec<MappedSharedMutexReadGuard<Any>>= vec![];
-		let mut mut_locks : Vec<MappedSharedMutexWriteGuard<Any>>= vec![];
-
 		// we obtain the correct component locks
-		for typ in component_types {
-			locks.push(components::get_components_read_lock(typ));
-		}
-		for typ in mut_component_types {
-			mut_locks.push(components::get_components_write_lock(typ));
-		}
+		let (locks, mut_locks) = components::get_components_locks(&component_types, &mut_component_types);
 		
-		// we run the handlers
-		handler.run(locks, mut_locks)
+		#[cfg(feature = "tracing")]
+		let run_start = Instant::now();
+
+		// we run the handlers, triggers from within a handler are not external
+		{
+			let _internal = InternalTriggers::start();
+			handler.run(locks, mut_locks);
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::handler(&tracing::HandlerSpan {
+			tick: current_tick(),
+			system_name: system_name,
+			event_name: &event.name,
+			data_count: data_count,
+			lock_wait: run_start - lock_start,
+			run_time: run_start.elapsed()
+		});
 	}
+
+	#[cfg(feature = "tracing")]
+	tracing::event_batch(&tracing::BatchSpan {
+		tick: current_tick(),
+		event_name: &event.name,
+		handlers: handler_count,
+		duration: batch_start.elapsed()
+	});
 }
 
 // Progresses the system to the next tick.
@@ -105,126 +565,290 @@ pub fn run_events() {
 // scheduled to run only once per tick are triggered.
 // A good scheme could be to run `next_tick` every 16ms, while `run_events` is ran continuously.
 pub fn next_tick() {
-	let mut this_tick_events = THIS_TICK_NEW_EVENTS.write().expect("THIS_TICK_NEW_EVENTS mutex was corrupted");
-	let mut next_events_lock = NEXT_TICK_NEW_EVENTS.write().expect("THIS_TICK_NEW_EVENTS mutex was corrupted");
+	EVENTS_THIS_TICK.store(0, Ordering::SeqCst);
+	CURRENT_TICK.fetch_add(1, Ordering::SeqCst);
+
+	let next_events : Vec<(String, AdvanceFn)>;
+	{
+		let mut next_events_lock = NEXT_TICK_NEW_EVENTS.write().expect("NEXT_TICK_NEW_EVENTS mutex was corrupted");
+		next_events = next_events_lock.drain().collect();
+	}
 
-	for next_event in next_events_lock.drain() {
-		this_tick_events.insert(next_event);
+	// advancing triggers the due events for this tick, events that still have data
+	// waiting for a later tick are queued again.
+	for (event_name, advance) in next_events {
+		if advance() {
+			trigger_next_tick(&event_name, advance);
+		}
 	}
 }
 
+/// Defines an event module with its data, queue and trigger function.
+///
+/// An optional coalescing policy decides what happens when the event is triggered
+/// more than once in a tick, it is one of the `events::Coalesce` variants:
+///
+/// event!{ mouse_move, x: i64, y: i64 ; coalesce = Latest }
+/// event!{ damage, amount: u32 ; coalesce = Merge(super::add_damage) }
+///
+/// Attributes can be put on the `Data` struct and its fields, no traits are derived
+/// by default:
+///
+/// event!{ #[derive(Debug, Clone)] aim, x: f32, #[doc="Upwards is positive."] y: f32 }
 #[macro_export]
 macro_rules! event {
-	( $name:ident, $( $field_name:ident : $field_typ:ty ),* ) => (
+	( @attributes [$($attr:tt)*] [$($item:tt)*] [] $name:ident, $( $(#[$field_attr:meta])* $field_name:ident : $field_typ:ty ),* ) => (
+		$crate::event!{ @attributes [$($attr)*] [$($item)*] [] $name, $( $(#[$field_attr])* $field_name : $field_typ ),* ; coalesce = All }
+	);
+
+	( @attributes [$($attr:tt)*] [$($item:tt)*] [] $name:ident, $( $(#[$field_attr:meta])* $field_name:ident : $field_typ:ty ),* ; coalesce = $policy:expr ) => (
 		pub mod $name {
-			use shared_mutex::{ SharedMutex, MappedSharedMutexWriteGuard, MappedSharedMutexReadGuard };
-			use std::any::{ Any, TypeId };
-			use $crate::events;
-			use uuid::Uuid;
-			use std::sync::Arc;
+			$($item)*
 
-			/// Example component
-			#[derive(PartialEq,Eq,Clone)]
+			/// The data of a single trigger, handlers receive all data of a tick
+			/// in one shared buffer so it does not need to be cloned.
+			$($attr)*
 			pub struct Data {
-				$(pub $field_name : $field_typ),*
+				$( $(#[$field_attr])* pub $field_name : $field_typ ),*
 			}
 
-			pub type HandlerFn = fn(Arc<Any>, &Vec<Data>, Vec<MappedSharedMutexReadGuard<Any>>, Vec<MappedSharedMutexWriteGuard<Any>>);
+			$crate::event_body!{ $( $field_name : $field_typ ),* ; coalesce = $policy }
+		}
+	);
 
-			pub struct Handler {
-				handler_fn: HandlerFn,
-				state: Arc<Any+Sync+Send>,
-				component_types: Vec<TypeId>,
-				mut_component_types: Vec<TypeId>
+	( $($body:tt)* ) => (
+		$crate::struct_attributes!{ event [] [] [] $($body)* }
+	)
+}
+
+/// Generates the queues, handlers and trigger functions of an event module. It expects
+/// a `Data` type with the given fields to be in scope, it is used by `event!` and `#[event]`.
+#[macro_export]
+macro_rules! event_body {
+	( $( $field_name:ident : $field_typ:ty ),* ; coalesce = $policy:expr ) => (
+		use $crate::shared_mutex::{ SharedMutex, MappedSharedMutexWriteGuard, MappedSharedMutexReadGuard };
+		use std::any::{ Any, TypeId };
+		use $crate::events;
+		use $crate::systems;
+		use std::sync::{ Arc, Once };
+
+		pub type HandlerFn = fn(Arc<Any>, &[Data], Vec<MappedSharedMutexReadGuard<Any>>, Vec<MappedSharedMutexWriteGuard<Any>>);
+
+		pub struct Handler {
+			instance: systems::InstanceID,
+			system: &'static str,
+			handler_fn: HandlerFn,
+			state: Arc<Any+Sync+Send>,
+			component_types: Vec<TypeId>,
+			mut_component_types: Vec<TypeId>
+		}
+
+		pub struct HandlerInstance {
+			system: &'static str,
+			handler_fn: HandlerFn,
+			state: Arc<Any+Sync+Send>,
+			component_types: Vec<TypeId>,
+			mut_component_types: Vec<TypeId>,
+			data: Arc<[Data]>
+		}
+
+		impl HandlerInstance {
+			pub fn new(h: &Handler, d: Arc<[Data]>) -> HandlerInstance {
+				HandlerInstance {
+					system: h.system,
+					handler_fn: h.handler_fn,
+					state: h.state.clone(),
+					// TODO are these clones really necessary? would be cool if they could be static refs
+					component_types: h.component_types.clone(),
+					mut_component_types: h.mut_component_types.clone(),
+					data: d
+				}
 			}
+		}
 
-			pub struct HandlerInstance {
-				handler_fn: HandlerFn,
-				state: Arc<Any+Sync+Send>,
-				component_types: Vec<TypeId>,
-				mut_component_types: Vec<TypeId>,
-				data: Vec<Data>
+		impl events::Handler for HandlerInstance {
+			fn run(&self, components: Vec<MappedSharedMutexReadGuard<Any>>, mut_components: Vec<MappedSharedMutexWriteGuard<Any>>) {
+				let handler_fn = self.handler_fn;
+				let data = &*self.data;
+				let state = self.state.clone();
+				handler_fn(state, data, components, mut_components) 
 			}
 
-			impl HandlerInstance {
-				pub fn new(h: &Handler, d: Vec<Data>) -> HandlerInstance {
-					HandlerInstance {
-						handler_fn: h.handler_fn,
-						state: h.state.clone(),
-						// TODO are these clones really necessary? would be cool if they could be static refs
-						component_types: h.component_types.clone(),
-						mut_component_types: h.mut_component_types.clone(),
-						data: d
-					}
-				}
+			fn component_types(&self) -> Vec<TypeId> { self.component_types.clone() }
+			fn mut_component_types(&self) -> Vec<TypeId> { self.mut_component_types.clone() }
+			fn system_name(&self) -> &'static str { self.system }
+			fn data_count(&self) -> usize { self.data.len() }
+		}
+
+		$crate::lazy_static::lazy_static! {
+			pub static ref HANDLERS: SharedMutex<Vec<Handler>> = SharedMutex::new(vec![]);
+			pub static ref THIS_TICK_DATA: SharedMutex<Vec<Data>> = SharedMutex::new(vec![]);
+			/// Data waiting to be triggered, along with the number of ticks left to wait.
+			pub static ref NEXT_TICK_DATA: SharedMutex<Vec<(u64, Data)>> = SharedMutex::new(vec![]);
+		}
+
+		/// EVENT_NAME is used to index events, it is the module path of the event.
+		pub const EVENT_NAME: &'static str = module_path!();
+
+		/// Names this event as a type, for services that work with any event.
+		pub struct Event;
+
+		impl events::EventType for Event {
+			type Data = Data;
+
+			fn name() -> &'static str { EVENT_NAME }
+			fn trigger_data_after(ticks: u64, data: Data) { trigger_data_after(ticks, data) }
+			fn deliver(data: Data) { push_this_tick(data) }
+			fn register() { register() }
+			fn advance_tick() -> bool { advance_tick() }
+			fn this_tick_data() -> &'static SharedMutex<Vec<Data>> { &THIS_TICK_DATA }
+			fn next_tick_data() -> &'static SharedMutex<Vec<(u64, Data)>> { &NEXT_TICK_DATA }
+		}
+
+		/// The policy used to combine multiple triggers within a tick.
+		pub fn coalesce_policy() -> events::Coalesce<Data> {
+			#[allow(unused_imports)]
+			use $crate::events::Coalesce::*;
+			$policy
+		}
+
+		/// Listeners are a list of functions that should be called by trigger
+		pub fn trigger($($field_name : $field_typ),*) {
+			let argument = Data {
+				$($field_name : $field_name),*
+			};
+
+			trigger_data_after(0, argument);
+		}
+
+		/// Queues the event to be triggered at the start of the next tick.
+		pub fn trigger_next_tick($($field_name : $field_typ),*) {
+			trigger_after(1, $($field_name),*);
+		}
+
+		/// Queues the event to be triggered after the given number of ticks,
+		/// `trigger_after(0, ..)` triggers it this tick.
+		pub fn trigger_after(ticks: u64, $($field_name : $field_typ),*) {
+			let argument = Data {
+				$($field_name : $field_name),*
+			};
+
+			trigger_data_after(ticks, argument);
+		}
+
+		pub fn trigger_data(argument: Data) {
+			trigger_data_after(0, argument);
+		}
+
+		pub fn trigger_data_after(ticks: u64, argument: Data) {
+			events::observe_trigger(EVENT_NAME, ticks, &argument);
+
+			if ticks == 0 {
+				return push_this_tick(argument);
 			}
 
-			impl events::Handler for HandlerInstance {
-				fn run(&self, components: Vec<MappedSharedMutexReadGuard<Any>>, mut_components: Vec<MappedSharedMutexWriteGuard<Any>>) {
-					let handler_fn = self.handler_fn;
-					let data = &self.data;
-					let state = self.state.clone();
-					handler_fn(state, data, components, mut_components) 
+			let mut data = NEXT_TICK_DATA.write().expect("NEXT_TICK_DATA mutex corrupted");
+			data.push((ticks, argument));
+
+			events::trigger_next_tick(EVENT_NAME, advance_tick);
+		}
+
+		fn push_this_tick(argument: Data) {
+			register();
+
+			let mut data = THIS_TICK_DATA.write().expect("THIS_TICK_DATA mutex corrupted");
+			coalesce_policy().push(&mut *data, argument);
+
+			events::trigger_this_tick(EVENT_NAME);
+		}
+
+		/// Triggers the queued data that is due this tick, returns whether there
+		/// is data left for later ticks.
+		pub fn advance_tick() -> bool {
+			let mut due = vec![];
+			let waiting;
+			{
+				let mut data = NEXT_TICK_DATA.write().expect("NEXT_TICK_DATA mutex corrupted");
+				let queued : Vec<(u64, Data)> = data.drain(..).collect();
+				for (ticks, argument) in queued {
+					if ticks <= 1 {
+						due.push(argument);
+					} else {
+						data.push((ticks - 1, argument));
+					}
 				}
+				waiting = !data.is_empty();
+			}
 
-				fn component_types(&self) -> Vec<TypeId> { self.component_types.clone() }
-				fn mut_component_types(&self) -> Vec<TypeId> { self.mut_component_types.clone() }
+			for argument in due {
+				push_this_tick(argument);
 			}
+			waiting
+		}
 
-			lazy_static! {
-				/// EVENT_UUID is used internally to index events, is randomly
-				/// generated at first access.
-				pub static ref EVENT_UUID: String = Uuid::new_v4().simple().to_string();
-				pub static ref HANDLERS: SharedMutex<Vec<Handler>> = SharedMutex::new(vec![]);
-				pub static ref THIS_TICK_DATA: SharedMutex<Vec<Data>> = SharedMutex::new(vec![]);
+		pub fn register_handler(instance: systems::InstanceID, system: &'static str, state: Arc<Any+Sync+Send>, handler_fn: HandlerFn, component_types: Vec<TypeId>, mut_component_types: Vec<TypeId>) {
+			if let Err(typ) = $crate::components::check_component_types(&component_types, &mut_component_types) {
+				panic!("Handler of {} for {} locks component {:?} more than once", system, EVENT_NAME, typ);
 			}
 
-			/// Listeners are a list of functions that should be called by trigger
-			pub fn trigger($($field_name : $field_typ),*) {
-				let argument = Data {
-					$($field_name : $field_name),*
-				};
+			let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
+			let handler = Handler {
+				instance: instance,
+				system: system,
+				handler_fn : handler_fn,
+				state: state,
+				// TODO are these clones really necessary? would be cool if they could be static refs
+				component_types : component_types.clone(),
+				mut_component_types : mut_component_types.clone()
+			};
+			handlers.push(handler);
+
+			register();
+		}
 
-				let mut data = THIS_TICK_DATA.write().expect("THIS_TICK_DATA mutex corrupted");
-				data.push(argument);
+		static REGISTER: Once = Once::new();
 
-				events::trigger_this_tick(&*EVENT_UUID);
-			}
+		/// Registers the event so it can be looked up by name. Events are also
+		/// registered on first use, so their queues are drained even when no
+		/// handlers have been registered.
+		pub fn register() {
+			REGISTER.call_once(register_event);
+		}
 
-			pub fn register_handler(state: Arc<Any+Sync+Send>, handler_fn: HandlerFn, component_types: Vec<TypeId>, mut_component_types: Vec<TypeId>) {
-				let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
-				let handler = Handler {
-					handler_fn : handler_fn,
-					state: state,
-					// TODO are these clones really necessary? would be cool if they could be static refs
-					component_types : component_types.clone(),
-					mut_component_types : mut_component_types.clone()
-				};
-				handlers.push(handler);
+		fn register_event() {
+			let event = events::Event {
+				name: EVENT_NAME.to_string(),
+				type_id: TypeId::of::<Data>(),
+				data_count: data_count,
+				get_handler_instances: get_handler_instances
+			};
 
-				let event = events::Event {
-					name: (&*EVENT_UUID).clone(),
-					get_handler_instances: get_handler_instances
-				};
+			events::register_event(event)
+		}
 
-				events::register_event(event)
-			}
+		pub fn data_count() -> usize {
+			THIS_TICK_DATA.read().expect("TICK DATA mutex corrupted").len()
+		}
 
-			pub fn get_handler_instances() -> Vec<Box<events::Handler>> {
-				let mut data_old = THIS_TICK_DATA.write().expect("TICK DATA mutex corrupted");
-				let data : Vec<Data> = data_old.drain(..).collect();
+		pub fn get_handler_instances() -> Vec<Box<events::Handler>> {
+			let mut data_old = THIS_TICK_DATA.write().expect("TICK DATA mutex corrupted");
+			let data : Arc<[Data]> = data_old.drain(..).collect::<Vec<Data>>().into();
 
-				let handlers_lock = HANDLERS.read().expect("HANDLERS mutex corrupted");
-				handlers_lock.iter().map(|h|
-					//TODO eliminate this data.clone
-					Box::new(HandlerInstance::new(h, data.clone())) as Box<events::Handler>
-				).collect()
-			}
+			let handlers_lock = HANDLERS.read().expect("HANDLERS mutex corrupted");
+			handlers_lock.iter().map(|h|
+				Box::new(HandlerInstance::new(h, data.clone())) as Box<events::Handler>
+			).collect()
+		}
 
-			pub fn clear_handlers() {
-				let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
-				handlers.clear();
-			}
+		/// Removes all handlers that were registered by the given system instance.
+		pub fn unregister_handlers(instance: systems::InstanceID) {
+			let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
+			handlers.retain(|h| h.instance != instance);
+		}
+
+		pub fn clear_handlers() {
+			let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
+			handlers.clear();
 		}
 	)
 }
@@ -233,11 +857,13 @@ macro_rules! event {
 macro_rules! sync_event {
 	( $name:ident, $field_name:ident : $field_typ:ty ) => (
 		pub mod $name {
-			use shared_mutex::SharedMutex;
+			use $crate::shared_mutex::SharedMutex;
 			use std::sync::Arc;
 			use std::any::{ Any };
+			use $crate::systems;
 
 			pub struct Handler {
+				pub instance: systems::InstanceID,
 				pub handler_fn: HandlerFn,
 				pub state: Arc<Any+Send+Sync>
 			}
@@ -245,7 +871,7 @@ macro_rules! sync_event {
 			pub type Argument<'a,'b> = $field_typ;
 			pub type HandlerFn = fn(Arc<Any+Send+Sync>, Argument);
 
-			lazy_static! {
+			$crate::lazy_static::lazy_static! {
 				pub static ref HANDLERS: SharedMutex<Vec<Handler>> = SharedMutex::new(vec![]);
 			}
 
@@ -258,15 +884,22 @@ macro_rules! sync_event {
 				}
 			}
 
-			pub fn register_handler(state: Arc<Any+Send+Sync>, handler_fn: HandlerFn) {
+			pub fn register_handler(instance: systems::InstanceID, state: Arc<Any+Send+Sync>, handler_fn: HandlerFn) {
 				let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
 				let handler = Handler {
+					instance: instance,
 					handler_fn: handler_fn,
 					state: state
 				};
 				handlers.push(handler);
 			}
 
+			/// Removes all handlers that were registered by the given system instance.
+			pub fn unregister_handlers(instance: systems::InstanceID) {
+				let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
+				handlers.retain(|h| h.instance != instance);
+			}
+
 			pub fn clear_handlers() {
 				let mut handlers = HANDLERS.write().expect("Events HANDLERS mutex corrupted");
 				handlers.clear();
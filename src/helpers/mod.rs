@@ -5,9 +5,9 @@ macro_rules! static_any_vec_map {
 			use std::collections::{ HashMap };
 			use std::iter::FromIterator;
 			use std::any::Any;
-			use shared_mutex::{ SharedMutex, MappedSharedMutexReadGuard };
+			use $crate::shared_mutex::{ SharedMutex, MappedSharedMutexReadGuard };
 
-			lazy_static! {
+			$crate::lazy_static::lazy_static! {
 				pub static ref MAP: SharedMutex<HashMap<$t, Box<Any+'static+Sync>>> = SharedMutex::new(HashMap::new());
 			}
 
@@ -0,0 +1,199 @@
+/// The hierarchy attaches entities to a parent entity, like weapons to a unit or
+/// buildings to a city. The parent of an entity is stored in the `parent` component
+/// and the children of an entity in the `children` component, both are kept up to
+/// date by the functions in this module, so they should not be changed directly.
+/// Handlers can read them like any other component:
+///
+/// on fire, {}, { parents: parent }, (self, data) => { .. }
+///
+/// Despawning an entity detaches it from its parent and leaves its children without
+/// a parent, `despawn_recursive` despawns the children as well.
+///
+/// Both components have a codec, so the hierarchy is saved in snapshots.
+///
+use std::any::TypeId;
+use std::error::Error;
+use std::fmt;
+use std::sync::Once;
+use shared_mutex::MappedSharedMutexWriteGuard;
+
+use codec::{ Codec, DecodeError };
+use components;
+use entities::{ self, EntityID };
+
+/// The parent of an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub EntityID);
+
+/// The children of an entity, in the order they were attached.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Children(pub Vec<EntityID>);
+
+impl Codec for Parent {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.0.encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<Parent, DecodeError> {
+		Ok(Parent(EntityID::decode(input)?))
+	}
+}
+
+impl Codec for Children {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.0.encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<Children, DecodeError> {
+		Ok(Children(Vec::decode(input)?))
+	}
+}
+
+component!{ parent: super::Parent }
+component!{ children: super::Children }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyError {
+	/// The parent is the child itself or one of its descendants.
+	Cycle { child: EntityID, parent: EntityID }
+}
+
+impl fmt::Display for HierarchyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			HierarchyError::Cycle { child, parent } => write!(f, "Entity {} can not be the parent of its ancestor {}", parent, child)
+		}
+	}
+}
+
+impl Error for HierarchyError {}
+
+static REGISTER: Once = Once::new();
+
+/// Registers the hierarchy components, their codecs and the despawn hook. The functions in this
+/// module call this, it only has to be called before handlers use the components.
+pub fn register() {
+	REGISTER.call_once(|| {
+		parent::register();
+		children::register();
+		components::register_codec::<parent::List>();
+		components::register_codec::<children::List>();
+		entities::on_despawn(detach_despawned);
+	});
+}
+
+fn lock<'mutex>() -> (MappedSharedMutexWriteGuard<'mutex, parent::Storage>, MappedSharedMutexWriteGuard<'mutex, children::Storage>) {
+	register();
+	let (_, mut locks) = components::get_components_locks(&[], &[TypeId::of::<parent::Component>(), TypeId::of::<children::Component>()]);
+	let children = locks.pop().expect("Children lock was not obtained")
+		.map(|v| v.downcast_mut().expect("Children list not of expected type."));
+	let parents = locks.pop().expect("Parent lock was not obtained")
+		.map(|v| v.downcast_mut().expect("Parent list not of expected type."));
+	(parents, children)
+}
+
+fn find_parent(parents: &parent::Storage, entity: EntityID) -> Option<EntityID> {
+	parents.iter().find(|&&(e, _)| e == entity).map(|&(_, Parent(p))| p)
+}
+
+/// Removes the entity from the children of its parent, returns the parent.
+fn detach(parents: &mut parent::Storage, children: &mut children::Storage, entity: EntityID) -> Option<EntityID> {
+	let old_parent = find_parent(parents, entity);
+	if let Some(old_parent) = old_parent {
+		parents.retain(|&(e, _)| e != entity);
+		for &mut (e, Children(ref mut list)) in children.iter_mut() {
+			if e == old_parent {
+				list.retain(|&c| c != entity);
+			}
+		}
+		children.retain(|&(_, Children(ref list))| !list.is_empty());
+	}
+	old_parent
+}
+
+/// Attaches the child to the parent, detaching it from its previous parent.
+pub fn set_parent(child: EntityID, parent: EntityID) -> Result<(), HierarchyError> {
+	let (mut parents, mut children) = lock();
+
+	let mut ancestor = Some(parent);
+	while let Some(a) = ancestor {
+		if a == child {
+			return Err(HierarchyError::Cycle { child: child, parent: parent });
+		}
+		ancestor = find_parent(&parents, a);
+	}
+
+	detach(&mut parents, &mut children, child);
+	parents.push((child, Parent(parent)));
+	match children.iter_mut().find(|&&mut (e, _)| e == parent) {
+		Some(&mut (_, Children(ref mut list))) => list.push(child),
+		None => children.push((parent, Children(vec![child])))
+	}
+	Ok(())
+}
+
+/// Detaches the entity from its parent, returns the parent it had.
+pub fn remove_parent(entity: EntityID) -> Option<EntityID> {
+	let (mut parents, mut children) = lock();
+	detach(&mut parents, &mut children, entity)
+}
+
+pub fn parent_of(entity: EntityID) -> Option<EntityID> {
+	register();
+	let parents = parent::LIST.read().expect("COMPONENT_LIST corrupted");
+	find_parent(&parents, entity)
+}
+
+pub fn children_of(entity: EntityID) -> Vec<EntityID> {
+	register();
+	let children = children::LIST.read().expect("COMPONENT_LIST corrupted");
+	children.iter().find(|&&(e, _)| e == entity).map_or(vec![], |&(_, Children(ref list))| list.clone())
+}
+
+/// The parent of the entity, its parent and so on.
+pub fn ancestors(entity: EntityID) -> Vec<EntityID> {
+	register();
+	let parents = parent::LIST.read().expect("COMPONENT_LIST corrupted");
+	let mut ancestors = vec![];
+	let mut ancestor = find_parent(&parents, entity);
+	while let Some(a) = ancestor {
+		ancestors.push(a);
+		ancestor = find_parent(&parents, a);
+	}
+	ancestors
+}
+
+/// The children of the entity and their descendants, every entity is followed by its
+/// own descendants.
+pub fn descendants(entity: EntityID) -> Vec<EntityID> {
+	register();
+	let children = children::LIST.read().expect("COMPONENT_LIST corrupted");
+	let mut descendants = vec![];
+	let mut stack = vec![entity];
+	while let Some(e) = stack.pop() {
+		if e != entity {
+			descendants.push(e);
+		}
+		if let Some(&(_, Children(ref list))) = children.iter().find(|&&(p, _)| p == e) {
+			stack.extend(list.iter().rev());
+		}
+	}
+	descendants
+}
+
+/// Despawns the entity along with all of its descendants.
+pub fn despawn_recursive(entity: EntityID) {
+	for descendant in descendants(entity).into_iter().rev() {
+		entities::despawn(descendant);
+	}
+	entities::despawn(entity);
+}
+
+fn detach_despawned(entity: EntityID) {
+	let (mut parents, mut children) = lock();
+	detach(&mut parents, &mut children, entity);
+
+	let orphans = children.iter().find(|&&(e, _)| e == entity).map_or(vec![], |&(_, Children(ref list))| list.clone());
+	parents.retain(|&(e, _)| !orphans.contains(&e));
+	children.retain(|&(e, _)| e != entity);
+}
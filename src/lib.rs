@@ -1,5 +1,3 @@
-#![feature(type_macros)]
-
 //! # Goal
 //!
 //! We want to define components, store them in an efficient manner and
@@ -24,7 +22,7 @@
 
 //! # Syntax
 //! 
-//! ``` 
+//! ```text
 //! component! { Physics, body: physics.RigidBody, physics_id: physics.ID }
 //!
 //! // event! { GameStarted } // This one is implicitly defined
@@ -65,31 +63,69 @@
 //! }}
 //! ```
 #[macro_use]
-extern crate lazy_static;
-extern crate shared_mutex;
-extern crate uuid;
+pub extern crate lazy_static;
+pub extern crate shared_mutex;
+#[cfg(feature = "serde")]
+pub extern crate serde;
 
 #[macro_use]
 pub mod helpers;
 #[macro_use]
+pub mod codec;
+#[macro_use]
 pub mod components;
 pub mod entities;
+pub mod prefabs;
+pub mod hierarchy;
+pub mod relations;
 #[macro_use]
 pub mod events;
 #[macro_use]
 pub mod systems;
+pub mod timers;
+pub mod recording;
+pub mod snapshot;
+pub mod delta;
+#[cfg(feature = "replication")]
+pub mod replication;
+#[cfg(feature = "serde")]
+pub mod serialization;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+#[cfg(feature = "tracing")]
+pub mod stats;
 
+use std::panic;
 use std::thread;
 pub use std::time::{ Duration, Instant };
 
 event!{ tick, step: super::Duration }
 
-pub fn run(ticks_per_second: u32) {
+// ticks are recorded like external input, so `on tick` handlers run again on replay
+codec!{ tick::Data { step } }
+
+/// Runs the ticker and waits for it, it only returns when a tick exceeds one of the
+/// event limits.
+pub fn run(ticks_per_second: u32) -> Result<(), events::CascadeError> {
 	let events_thread = ticker(ticks_per_second, true);
-	let _ = events_thread.join();
+	match events_thread.join() {
+		Ok(result) => result,
+		Err(panic) => panic::resume_unwind(panic)
+	}
+}
+
+/// Runs a single tick of the given step. The ticker calls this in real time, but it
+/// can also be called directly to step the simulation deterministically.
+pub fn tick_once(step: Duration) -> Result<(), events::CascadeError> {
+	tick::trigger(step);
+	timers::advance(step);
+	events::next_tick();
+	events::run_tick()
 }
 
-pub fn ticker(ticks_per_second: u32, sleep: bool) -> thread::JoinHandle<()> {
+/// Runs ticks in real time on a new thread. The thread stops at the first tick that
+/// exceeds one of the event limits and returns its error.
+pub fn ticker(ticks_per_second: u32, sleep: bool) -> thread::JoinHandle<Result<(), events::CascadeError>> {
 	let step = Duration::from_secs(1) / ticks_per_second;
 	
 	let mut last_tick = Instant::now();
@@ -103,10 +139,8 @@ pub fn ticker(ticks_per_second: u32, sleep: bool) -> thread::JoinHandle<()> {
 					thread::sleep(Duration::from_millis(1));
 				}
 			} else {
-				tick::trigger(step);
-				events::next_tick();
 				last_tick = Instant::now();
-				events::run_events();
+				tick_once(step)?;
 			}
 		}
 	})
@@ -0,0 +1,374 @@
+/// Prefabs are named sets of component initializers that entities can be spawned
+/// from. Each spawn runs the initializers again, so every entity gets its own
+/// components, and single components can be overridden per instance:
+///
+/// prefabs::register(Prefab::new("soldier")
+///   .with_default::<position::Component>()
+///   .with(|| health::Component { hp: 10 })
+///   .tag::<player::Component>());
+///
+/// let captain = prefabs::instantiate("soldier").expect("No soldier prefab")
+///   .set(|h: &mut health::Component| h.hp = 20).expect("Soldiers have health")
+///   .spawn();
+///
+/// Prefabs can also be loaded from data files. Components are made available to the
+/// files by registering the fields that can be set, the other fields keep their
+/// default values:
+///
+/// prefab_component!{ health, hp }
+/// prefabs::register_tag::<player::Component>("player");
+///
+/// # A file can hold many prefabs
+/// [soldier]
+/// position
+/// health hp=10
+/// player
+///
+/// Values are parsed with `FromStr`, values with spaces are put in double quotes.
+///
+use std::any::{ Any, TypeId };
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{ self, Read };
+use std::path::Path;
+use std::sync::Arc;
+use shared_mutex::SharedMutex;
+
+use entities::{ EntityBuilder, EntityID };
+
+type MakeFn = Arc<Fn() -> Box<Any> + Send + Sync>;
+type AddFn = fn(EntityBuilder, Box<Any>) -> EntityBuilder;
+
+/// Sets a field of a component from its text in a data file.
+pub type FieldSetter<C> = fn(&mut C, &str, &str) -> Result<(), String>;
+
+#[derive(Clone)]
+struct Part {
+	typ: TypeId,
+	make: MakeFn,
+	add: AddFn
+}
+
+/// A named set of component initializers.
+#[derive(Clone)]
+pub struct Prefab {
+	name: String,
+	parts: Vec<Part>
+}
+
+type LoaderFn = Arc<Fn(&[(String, String)]) -> Result<Part, String> + Send + Sync>;
+
+lazy_static! {
+	static ref PREFABS: SharedMutex<HashMap<String, Prefab>> = SharedMutex::new(HashMap::new());
+	static ref LOADERS: SharedMutex<HashMap<String, LoaderFn>> = SharedMutex::new(HashMap::new());
+}
+
+fn add_component<C: Any>(builder: EntityBuilder, component: Box<Any>) -> EntityBuilder {
+	builder.with(*component.downcast::<C>().expect("Prefab component not of expected type."))
+}
+
+fn add_tag<T: Any>(builder: EntityBuilder, _: Box<Any>) -> EntityBuilder {
+	builder.tag::<T>()
+}
+
+impl Prefab {
+	pub fn new(name: &str) -> Prefab {
+		Prefab { name: name.to_string(), parts: vec![] }
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Adds a component that is created by the initializer on every spawn. Adding
+	/// a component of the same type again replaces it.
+	pub fn with<C, F>(self, init: F) -> Prefab where C: Any, F: Fn() -> C + Send + Sync + 'static {
+		self.part(Part {
+			typ: TypeId::of::<C>(),
+			make: Arc::new(move || Box::new(init()) as Box<Any>),
+			add: add_component::<C>
+		})
+	}
+
+	/// Adds a component with its default values.
+	pub fn with_default<C: Any + Default>(self) -> Prefab {
+		self.with(C::default)
+	}
+
+	/// Adds the tag of which `T` is the `Component` type.
+	pub fn tag<T: Any>(self) -> Prefab {
+		self.part(Part {
+			typ: TypeId::of::<T>(),
+			make: Arc::new(|| Box::new(()) as Box<Any>),
+			add: add_tag::<T>
+		})
+	}
+
+	fn part(mut self, part: Part) -> Prefab {
+		self.parts.retain(|p| p.typ != part.typ);
+		self.parts.push(part);
+		self
+	}
+
+	/// Runs the initializers, the instance can be changed before it is spawned.
+	pub fn instantiate(&self) -> Instance {
+		Instance {
+			components: self.parts.iter().map(|p| (p.typ, (p.make)(), p.add)).collect()
+		}
+	}
+
+	pub fn spawn(&self) -> EntityID {
+		self.instantiate().spawn()
+	}
+}
+
+/// The components of a single entity that is about to be spawned from a prefab.
+pub struct Instance {
+	components: Vec<(TypeId, Box<Any>, AddFn)>
+}
+
+impl Instance {
+	/// Adds a component, or replaces the one the prefab initialized.
+	pub fn with<C: Any>(mut self, component: C) -> Instance {
+		let typ = TypeId::of::<C>();
+		self.components.retain(|&(t, _, _)| t != typ);
+		self.components.push((typ, Box::new(component), add_component::<C>));
+		self
+	}
+
+	/// Changes a component the prefab initialized, returns `None` when the prefab
+	/// does not have a component of this type.
+	pub fn set<C: Any, F: FnOnce(&mut C)>(mut self, change: F) -> Option<Instance> {
+		{
+			let component = self.components.iter_mut()
+				.find(|&&mut (t, _, _)| t == TypeId::of::<C>())?;
+			change(component.1.downcast_mut::<C>().expect("Prefab component not of expected type."));
+		}
+		Some(self)
+	}
+
+	pub fn tag<T: Any>(mut self) -> Instance {
+		let typ = TypeId::of::<T>();
+		self.components.retain(|&(t, _, _)| t != typ);
+		self.components.push((typ, Box::new(()), add_tag::<T>));
+		self
+	}
+
+	/// Puts the components in a builder for a newly allocated entity.
+	pub fn builder(self) -> EntityBuilder {
+		self.components.into_iter().fold(EntityBuilder::new(), |builder, (_, component, add)| add(builder, component))
+	}
+
+	pub fn spawn(self) -> EntityID {
+		self.builder().spawn()
+	}
+}
+
+/// Registers the prefab under its name, replacing any prefab with the same name.
+pub fn register(prefab: Prefab) {
+	let mut prefabs = PREFABS.write().expect("PREFABS lock corrupted");
+	prefabs.insert(prefab.name.clone(), prefab);
+}
+
+pub fn get(name: &str) -> Option<Prefab> {
+	let prefabs = PREFABS.read().expect("PREFABS lock corrupted");
+	prefabs.get(name).cloned()
+}
+
+/// The names of all registered prefabs, sorted.
+pub fn names() -> Vec<String> {
+	let prefabs = PREFABS.read().expect("PREFABS lock corrupted");
+	let mut names : Vec<String> = prefabs.keys().cloned().collect();
+	names.sort();
+	names
+}
+
+pub fn instantiate(name: &str) -> Option<Instance> {
+	get(name).map(|prefab| prefab.instantiate())
+}
+
+pub fn spawn(name: &str) -> Option<EntityID> {
+	get(name).map(|prefab| prefab.spawn())
+}
+
+/// Makes a component available to prefab files under the given label, the setter
+/// is called for every field the file sets. Usually called through `prefab_component!`.
+pub fn register_component<C: Any + Default>(label: &str, setter: FieldSetter<C>) {
+	let loader : LoaderFn = Arc::new(move |fields: &[(String, String)]| {
+		let fields = fields.to_vec();
+		let make = move || -> Result<C, String> {
+			let mut component = C::default();
+			for (field, value) in fields.iter() {
+				setter(&mut component, field, value)?;
+			}
+			Ok(component)
+		};
+
+		// The fields are checked once when loading, so spawning can not fail.
+		make()?;
+		Ok(Part {
+			typ: TypeId::of::<C>(),
+			make: Arc::new(move || Box::new(make().expect("Prefab fields were checked when loading")) as Box<Any>),
+			add: add_component::<C>
+		})
+	});
+
+	let mut loaders = LOADERS.write().expect("LOADERS lock corrupted");
+	loaders.insert(label.to_string(), loader);
+}
+
+/// Makes a tag available to prefab files under the given label.
+pub fn register_tag<T: Any>(label: &str) {
+	let loader : LoaderFn = Arc::new(|fields: &[(String, String)]| {
+		if let Some((field, _)) = fields.first() {
+			return Err(format!("Tags have no fields, found {}", field));
+		}
+		Ok(Part {
+			typ: TypeId::of::<T>(),
+			make: Arc::new(|| Box::new(()) as Box<Any>),
+			add: add_tag::<T>
+		})
+	});
+
+	let mut loaders = LOADERS.write().expect("LOADERS lock corrupted");
+	loaders.insert(label.to_string(), loader);
+}
+
+/// Registers the fields of a component module that prefab files can set, the
+/// module's name is the label of the component in the files:
+///
+/// prefab_component!{ health, hp, max_hp }
+#[macro_export]
+macro_rules! prefab_component {
+	( $component_name:ident $(, $field:ident )* ) => (
+		$crate::prefabs::register_component::<$component_name::Component>(stringify!($component_name), |component, field, value| {
+			match field {
+				$( stringify!($field) => value.parse().map(|v| component.$field = v).map_err(|e| e.to_string()), )*
+				_ => {
+					let _ = (component, value);
+					Err(format!("Unknown field {}", field))
+				}
+			}
+		})
+	)
+}
+
+#[derive(Debug)]
+pub enum PrefabError {
+	Io(io::Error),
+	Syntax { line: usize, message: String },
+	UnknownComponent { line: usize, label: String },
+	Field { line: usize, label: String, message: String }
+}
+
+impl fmt::Display for PrefabError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			PrefabError::Io(ref error) => write!(f, "Could not read prefabs: {}", error),
+			PrefabError::Syntax { line, ref message } => write!(f, "Line {}: {}", line, message),
+			PrefabError::UnknownComponent { line, ref label } => write!(f, "Line {}: no component registered as {}", line, label),
+			PrefabError::Field { line, ref label, ref message } => write!(f, "Line {}: invalid {}: {}", line, label, message)
+		}
+	}
+}
+
+impl Error for PrefabError {}
+
+impl From<io::Error> for PrefabError {
+	fn from(error: io::Error) -> PrefabError {
+		PrefabError::Io(error)
+	}
+}
+
+/// Registers the prefabs in the file, returns their names. Nothing is registered
+/// when the file has an error.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<String>, PrefabError> {
+	let mut source = String::new();
+	File::open(path)?.read_to_string(&mut source)?;
+	load_str(&source)
+}
+
+pub fn load_str(source: &str) -> Result<Vec<String>, PrefabError> {
+	let mut prefabs : Vec<Prefab> = vec![];
+
+	{
+		let loaders = LOADERS.read().expect("LOADERS lock corrupted");
+		for (index, line) in source.lines().enumerate() {
+			let number = index + 1;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			if line.starts_with('[') {
+				let name = if line.ends_with(']') && line.len() > 1 { line[1..line.len() - 1].trim() } else { "" };
+				if name.is_empty() {
+					return Err(PrefabError::Syntax { line: number, message: format!("Expected a prefab name like [soldier], found {}", line) });
+				}
+				if prefabs.iter().any(|p| p.name == name) {
+					return Err(PrefabError::Syntax { line: number, message: format!("Prefab {} is defined twice", name) });
+				}
+				prefabs.push(Prefab::new(name));
+				continue;
+			}
+
+			let (label, fields) = parse_component(line).map_err(|message| PrefabError::Syntax { line: number, message: message })?;
+			let prefab = match prefabs.pop() {
+				Some(prefab) => prefab,
+				None => return Err(PrefabError::Syntax { line: number, message: format!("Component {} is not in a prefab", label) })
+			};
+			let loader = match loaders.get(&label) {
+				Some(loader) => loader,
+				None => return Err(PrefabError::UnknownComponent { line: number, label: label })
+			};
+			let part = loader(&fields).map_err(|message| PrefabError::Field { line: number, label: label, message: message })?;
+			prefabs.push(prefab.part(part));
+		}
+	}
+
+	let names = prefabs.iter().map(|p| p.name.clone()).collect();
+	for prefab in prefabs {
+		register(prefab);
+	}
+	Ok(names)
+}
+
+/// Splits a line like `label field=value name="Big Bob"` into the label and fields.
+fn parse_component(line: &str) -> Result<(String, Vec<(String, String)>), String> {
+	let mut words = vec![];
+	let mut word = String::new();
+	let mut quoted = false;
+
+	for c in line.chars() {
+		match c {
+			'"' => quoted = !quoted,
+			c if c.is_whitespace() && !quoted => {
+				if !word.is_empty() {
+					words.push(word);
+					word = String::new();
+				}
+			},
+			c => word.push(c)
+		}
+	}
+	if quoted {
+		return Err("Unterminated quote".to_string());
+	}
+	if !word.is_empty() {
+		words.push(word);
+	}
+
+	let mut words = words.into_iter();
+	let label = words.next().expect("Empty lines are skipped");
+	let mut fields = vec![];
+	for word in words {
+		let mut split = word.splitn(2, '=');
+		match (split.next(), split.next()) {
+			(Some(field), Some(value)) if !field.is_empty() => fields.push((field.to_string(), value.to_string())),
+			_ => return Err(format!("Expected field=value, found {}", word))
+		}
+	}
+	Ok((label, fields))
+}
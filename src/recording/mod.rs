@@ -0,0 +1,240 @@
+/// The recorder captures the external triggers of events, along with the tick they
+/// were triggered in, so a run can be reproduced by replaying them. Triggers from
+/// within event handlers and timers are not recorded, they happen again by running the
+/// handlers and advancing the timers.
+///
+/// Only events that have a codec registered with `events::register_codec` can be
+/// recorded, the names of other events that were triggered are collected so they
+/// can be reported. Recorders and replays register the codec of the `tick` event
+/// themselves, so the steps of `tick_once` are replayed as well.
+///
+/// let recorder = Recorder::start();
+/// // .. run the simulation
+/// recorder.stop().save("bug_report.rec")?;
+///
+/// let mut replay = Replay::new(Recording::load("bug_report.rec")?);
+/// replay.run()?;
+///
+/// Runs that use timers are replayed with the step they were run with, so the
+/// timers fire in the same ticks:
+///
+/// let mut replay = Replay::with_timers(Recording::load("bug_report.rec")?, step);
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{ self, Read, Write, BufReader, BufWriter };
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use shared_mutex::SharedMutex;
+
+use codec::{ self, Codec, DecodeError };
+use events;
+use timers;
+
+const MAGIC : &[u8] = b"ERREC";
+const VERSION : u32 = 1;
+
+/// A single recorded trigger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+	pub tick: u64,
+	pub delay: u64,
+	pub event_name: String,
+	pub data: Vec<u8>
+}
+
+codec!{ Record { tick, delay, event_name, data } }
+
+#[derive(Default)]
+struct Capture {
+	records: Vec<Record>,
+	unrecorded: BTreeSet<String>
+}
+
+pub struct Recorder {
+	observer: events::ObserverID,
+	capture: Arc<SharedMutex<Capture>>
+}
+
+impl Recorder {
+	pub fn start() -> Recorder {
+		events::register_codec::<::tick::Event>();
+		let capture = Arc::new(SharedMutex::new(Capture::default()));
+		let observer_capture = capture.clone();
+
+		let observer = events::add_trigger_observer(move |trigger| {
+			if !trigger.external {
+				return;
+			}
+
+			let mut capture = observer_capture.write().expect("Recorder capture corrupted");
+			match events::codec(trigger.event_name) {
+				Some(event_codec) => {
+					let mut data = vec![];
+					(event_codec.encode)(trigger.data, &mut data);
+					capture.records.push(Record {
+						tick: trigger.tick,
+						delay: trigger.delay,
+						event_name: trigger.event_name.to_string(),
+						data: data
+					});
+				},
+				None => {
+					capture.unrecorded.insert(trigger.event_name.to_string());
+				}
+			}
+		});
+
+		Recorder {
+			observer: observer,
+			capture: capture
+		}
+	}
+
+	/// The names of the events that were triggered but could not be recorded because
+	/// they have no codec.
+	pub fn unrecorded_events(&self) -> Vec<String> {
+		let capture = self.capture.read().expect("Recorder capture corrupted");
+		capture.unrecorded.iter().cloned().collect()
+	}
+
+	pub fn stop(self) -> Recording {
+		events::remove_trigger_observer(self.observer);
+		let mut capture = self.capture.write().expect("Recorder capture corrupted");
+		Recording { records: capture.records.drain(..).collect() }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Recording {
+	pub records: Vec<Record>
+}
+
+impl Recording {
+	pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		let mut out = MAGIC.to_vec();
+		VERSION.encode(&mut out);
+		self.records.encode(&mut out);
+		writer.write_all(&out)
+	}
+
+	pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Recording> {
+		let mut bytes = vec![];
+		reader.read_to_end(&mut bytes)?;
+
+		if !bytes.starts_with(MAGIC) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Not an event recording"));
+		}
+
+		let (version, records) : (u32, Vec<Record>) = codec::from_bytes(&bytes[MAGIC.len()..])
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		if version != VERSION {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported recording version {}", version)));
+		}
+
+		Ok(Recording { records: records })
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		let mut writer = BufWriter::new(File::create(path)?);
+		self.write_to(&mut writer)?;
+		writer.flush()
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Recording> {
+		let mut reader = BufReader::new(File::open(path)?);
+		Recording::read_from(&mut reader)
+	}
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+	UnknownEvent(String),
+	Decode(String, DecodeError),
+	Cascade(events::CascadeError)
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReplayError::UnknownEvent(ref name) => write!(f, "Recorded event {} has no codec registered", name),
+			ReplayError::Decode(ref name, ref error) => write!(f, "Could not decode recorded event {}: {}", name, error),
+			ReplayError::Cascade(ref error) => write!(f, "{}", error)
+		}
+	}
+}
+
+impl Error for ReplayError {}
+
+/// Feeds a recording back through the event system tick by tick. Every step triggers
+/// the records of one recorded tick, advances the timers, then progresses to the
+/// next tick and runs its events, the same order the ticker uses.
+pub struct Replay {
+	records: Vec<Record>,
+	position: usize,
+	tick: u64,
+	timer_step: Option<Duration>
+}
+
+impl Replay {
+	pub fn new(recording: Recording) -> Replay {
+		events::register_codec::<::tick::Event>();
+		let mut records = recording.records;
+		// the sort is stable so records within a tick keep their order
+		records.sort_by_key(|r| r.tick);
+		let tick = records.first().map(|r| r.tick).unwrap_or(0);
+
+		Replay {
+			records: records,
+			position: 0,
+			tick: tick,
+			timer_step: None
+		}
+	}
+
+	/// Replays the recording and advances the timers by the step every tick.
+	pub fn with_timers(recording: Recording, step: Duration) -> Replay {
+		let mut replay = Replay::new(recording);
+		replay.timer_step = Some(step);
+		replay
+	}
+
+	/// The recorded tick that will be replayed by the next step.
+	pub fn tick(&self) -> u64 {
+		self.tick
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.position >= self.records.len()
+	}
+
+	pub fn step(&mut self) -> Result<(), ReplayError> {
+		while self.position < self.records.len() && self.records[self.position].tick == self.tick {
+			let record = &self.records[self.position];
+			let event_codec = events::codec(&record.event_name)
+				.ok_or_else(|| ReplayError::UnknownEvent(record.event_name.clone()))?;
+
+			let mut input = &record.data[..];
+			(event_codec.trigger_after)(record.delay, &mut input)
+				.map_err(|e| ReplayError::Decode(record.event_name.clone(), e))?;
+			self.position += 1;
+		}
+
+		if let Some(step) = self.timer_step {
+			timers::advance(step);
+		}
+		self.tick += 1;
+		events::next_tick();
+		events::run_tick().map_err(ReplayError::Cascade)
+	}
+
+	/// Steps until every record has been replayed.
+	pub fn run(&mut self) -> Result<(), ReplayError> {
+		while !self.is_finished() {
+			self.step()?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,201 @@
+/// Relations connect a source entity to target entities, like a worker to the
+/// building it works at. They are declared like components and stored as pairs that
+/// can be queried from both ends:
+///
+/// relation!{ works_at }
+///
+/// works_at::add(worker, mill);
+/// works_at::targets(worker) == vec![mill]
+/// works_at::sources(mill) == vec![worker]
+///
+/// When either end is despawned its pairs are removed. A relation can name an event
+/// with `source` and `target` entity fields that is triggered for every pair that is
+/// removed that way:
+///
+/// event!{ target_lost, source: EntityID, target: EntityID }
+/// relation!{ targets ; on_broken = super::target_lost }
+///
+/// Handlers read relations like components, the `Storage` is a `Relations`.
+///
+use std::collections::BTreeSet;
+
+use codec::{ Codec, DecodeError };
+use components::EntityStorage;
+use entities::EntityID;
+
+/// A set of source and target pairs, indexed by both ends.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Relations {
+	forward: BTreeSet<(EntityID, EntityID)>,
+	backward: BTreeSet<(EntityID, EntityID)>
+}
+
+impl Relations {
+	pub fn new() -> Relations {
+		Relations::default()
+	}
+
+	/// Relates the source to the target, returns whether they were not related yet.
+	pub fn insert(&mut self, source: EntityID, target: EntityID) -> bool {
+		self.backward.insert((target, source));
+		self.forward.insert((source, target))
+	}
+
+	/// Returns whether the source was related to the target.
+	pub fn remove(&mut self, source: EntityID, target: EntityID) -> bool {
+		self.backward.remove(&(target, source));
+		self.forward.remove(&(source, target))
+	}
+
+	pub fn contains(&self, source: EntityID, target: EntityID) -> bool {
+		self.forward.contains(&(source, target))
+	}
+
+	/// The targets the source is related to, sorted.
+	pub fn targets(&self, source: EntityID) -> Vec<EntityID> {
+		self.forward.range((source, EntityID::MIN)..=(source, EntityID::MAX)).map(|&(_, t)| t).collect()
+	}
+
+	/// The sources that are related to the target, sorted.
+	pub fn sources(&self, target: EntityID) -> Vec<EntityID> {
+		self.backward.range((target, EntityID::MIN)..=(target, EntityID::MAX)).map(|&(_, s)| s).collect()
+	}
+
+	/// Removes every pair the entity is in, at either end, returns the removed pairs.
+	pub fn remove_entity(&mut self, entity: EntityID) -> Vec<(EntityID, EntityID)> {
+		let mut removed : Vec<(EntityID, EntityID)> = self.targets(entity).into_iter().map(|t| (entity, t)).collect();
+		removed.extend(self.sources(entity).into_iter().filter(|&s| s != entity).map(|s| (s, entity)));
+		for &(source, target) in removed.iter() {
+			self.remove(source, target);
+		}
+		removed
+	}
+
+	/// All pairs, sorted by source.
+	pub fn iter<'a>(&'a self) -> Box<Iterator<Item=(EntityID, EntityID)> + 'a> {
+		Box::new(self.forward.iter().cloned())
+	}
+
+	pub fn len(&self) -> usize {
+		self.forward.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.forward.is_empty()
+	}
+}
+
+/// Only the pairs are encoded, the index by target is rebuilt when decoding.
+impl Codec for Relations {
+	fn encode(&self, out: &mut Vec<u8>) {
+		self.forward.encode(out);
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<Relations, DecodeError> {
+		let mut relations = Relations::new();
+		for (source, target) in BTreeSet::<(EntityID, EntityID)>::decode(input)? {
+			relations.insert(source, target);
+		}
+		Ok(relations)
+	}
+}
+
+/// The items of an entity are the targets it is related to.
+impl EntityStorage for Relations {
+	type Item = EntityID;
+
+	fn entities<'a>(&'a self) -> Vec<(EntityID, Vec<&'a EntityID>)> {
+		let mut entities : Vec<(EntityID, Vec<&'a EntityID>)> = vec![];
+		for &(source, ref target) in self.forward.iter() {
+			match entities.last_mut() {
+				Some(&mut (s, ref mut targets)) if s == source => targets.push(target),
+				_ => entities.push((source, vec![target]))
+			}
+		}
+		entities
+	}
+
+	fn has_entity(&self, entity: EntityID) -> bool {
+		self.forward.range((entity, EntityID::MIN)..).next().is_some_and(|&(s, _)| s == entity)
+	}
+
+	fn set_entity(&mut self, entity: EntityID, targets: Vec<EntityID>) {
+		for target in self.targets(entity) {
+			self.remove(entity, target);
+		}
+		for target in targets {
+			self.insert(entity, target);
+		}
+	}
+}
+
+/// Defines a relation module, see the module documentation.
+#[macro_export]
+macro_rules! relation {
+	( @define [ $(#[$attr:meta])* ] $relation_name:ident, $broken:expr ) => (
+		$(#[$attr])*
+		pub mod $relation_name {
+			/// Identifies the relation in the components registry, no instances are stored.
+			pub struct Component;
+
+			pub type Storage = $crate::relations::Relations;
+
+			$crate::component_storage!{ @list }
+
+			/// Relates the source to the target, returns whether they were not related yet.
+			pub fn add(source: EntityID, target: EntityID) -> bool {
+				let mut list = write();
+				list.insert(source, target)
+			}
+
+			/// Removes every pair the entity is in, returns whether there were any.
+			pub fn remove(entity: EntityID) -> bool {
+				let removed = LIST.write().expect("COMPONENT_LIST corrupted").remove_entity(entity);
+				if !removed.is_empty() {
+					components::mark_written(TypeId::of::<Component>());
+				}
+				for &(source, target) in removed.iter() {
+					broken(source, target);
+				}
+				!removed.is_empty()
+			}
+
+			pub fn remove_pair(source: EntityID, target: EntityID) -> bool {
+				let mut list = LIST.write().expect("COMPONENT_LIST corrupted");
+				let removed = list.remove(source, target);
+				if removed {
+					components::mark_written(TypeId::of::<Component>());
+				}
+				removed
+			}
+
+			pub fn contains(source: EntityID, target: EntityID) -> bool {
+				let list = LIST.read().expect("COMPONENT_LIST corrupted");
+				list.contains(source, target)
+			}
+
+			pub fn targets(source: EntityID) -> Vec<EntityID> {
+				let list = LIST.read().expect("COMPONENT_LIST corrupted");
+				list.targets(source)
+			}
+
+			pub fn sources(target: EntityID) -> Vec<EntityID> {
+				let list = LIST.read().expect("COMPONENT_LIST corrupted");
+				list.sources(target)
+			}
+
+			#[allow(unused_variables)]
+			fn broken(source: EntityID, target: EntityID) {
+				($broken)(source, target)
+			}
+		}
+	);
+
+	( $(#[$attr:meta])* $relation_name:ident ) => (
+		$crate::relation!{ @define [ $(#[$attr])* ] $relation_name, |_, _| () }
+	);
+
+	( $(#[$attr:meta])* $relation_name:ident ; on_broken = $($event:ident)::+ ) => (
+		$crate::relation!{ @define [ $(#[$attr])* ] $relation_name, $($event)::+::trigger }
+	);
+}
@@ -0,0 +1,539 @@
+/// Replication mirrors the replicated components of a server world on client worlds,
+/// enabled by the `replication` feature. Components are marked as replicated on both
+/// ends, their lists need a codec:
+///
+/// components::register_codec::<position::List>();
+/// replication::replicate::<position::List>();
+///
+/// The server sends the changes since its last update to every client, a client that
+/// just connected gets all replicated components instead:
+///
+/// let mut server = Server::bind("127.0.0.1:7000")?;
+/// loop { entity_rust::tick_once(step)?; server.update()?; }
+///
+/// let mut client = Client::connect("127.0.0.1:7000")?;
+/// loop { client.poll()?; }
+///
+/// Changes are sent as deltas, see the `delta` module, each prefixed with its length
+/// as u64, frames longer than `MAX_FRAME_LEN` are rejected. A client that does not
+/// take the changes within the write timeout of the server is disconnected. Entities are spawned and despawned on the client along with their first
+/// and last replicated component.
+///
+/// In the other direction events can be forwarded, so client input drives the
+/// simulation on the server. Remote events are marked on both ends and need a codec:
+///
+/// events::register_codec::<move_order::Event>();
+/// replication::forward_event::<move_order::Event>();
+///
+/// let mut sender = EventSender::connect("127.0.0.1:7001", 2, client.tick_source())?;
+/// move_order::trigger(unit, x, y);
+/// sender.flush()?;
+///
+/// let mut receiver = EventReceiver::bind("127.0.0.1:7001")?;
+/// loop { events::next_tick(); receiver.poll()?; events::run_tick()?; }
+///
+/// The data is delivered on the receiver at the tick the sender asks for, it is the
+/// tick of its tick source plus the delays of the trigger and the sender. The tick
+/// source has to count the ticks of the receiver, like the server tick a replication
+/// client last received.
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::io::{ self, Read, Write };
+use std::net::{ SocketAddr, TcpListener, TcpStream, ToSocketAddrs };
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::time::Duration;
+use shared_mutex::SharedMutex;
+
+use codec::{ self, Codec, DecodeError };
+use components::ListType;
+use delta::{ Delta, DeltaError, Tracker };
+use events::{ self, EventType };
+
+/// The longest frame that is accepted, longer frames are reported as `FrameTooLong`.
+pub const MAX_FRAME_LEN : usize = 256 << 20;
+
+lazy_static! {
+	static ref REPLICATED: SharedMutex<BTreeSet<&'static str>> = SharedMutex::new(BTreeSet::new());
+	static ref REMOTE_EVENTS: SharedMutex<BTreeSet<&'static str>> = SharedMutex::new(BTreeSet::new());
+}
+
+/// Marks the list as replicated. Servers only replicate the lists that were marked
+/// before they were bound.
+pub fn replicate<L: ListType>() {
+	let mut replicated = REPLICATED.write().expect("REPLICATED lock corrupted");
+	replicated.insert(L::label());
+}
+
+/// The labels of the replicated lists, in alphabetical order.
+pub fn replicated() -> Vec<&'static str> {
+	let replicated = REPLICATED.read().expect("REPLICATED lock corrupted");
+	replicated.iter().cloned().collect()
+}
+
+fn write_frame(mut stream: &TcpStream, bytes: &[u8]) -> io::Result<()> {
+	let mut frame = Vec::with_capacity(bytes.len() + 8);
+	bytes.len().encode(&mut frame);
+	frame.extend_from_slice(bytes);
+	stream.write_all(&frame)
+}
+
+/// Accepts clients and sends them the changes to the replicated components.
+pub struct Server {
+	listener: TcpListener,
+	labels: Vec<&'static str>,
+	tracker: Tracker,
+	write_timeout: Duration,
+	joining: Vec<TcpStream>,
+	clients: Vec<TcpStream>
+}
+
+impl Server {
+	pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Server> {
+		let listener = TcpListener::bind(address)?;
+		listener.set_nonblocking(true)?;
+
+		let labels = replicated();
+		let mut tracker = Tracker::for_lists(&labels);
+		tracker.delta();
+
+		Ok(Server {
+			listener: listener,
+			labels: labels,
+			tracker: tracker,
+			write_timeout: Duration::from_secs(1),
+			joining: vec![],
+			clients: vec![]
+		})
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+
+	/// Limits how long an update waits for a client to take the changes, clients that
+	/// take longer are disconnected. The default is a second.
+	pub fn set_write_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+		for stream in self.joining.iter().chain(self.clients.iter()) {
+			stream.set_write_timeout(Some(timeout))?;
+		}
+		self.write_timeout = timeout;
+		Ok(())
+	}
+
+	/// Accepts the clients that are waiting to connect, without blocking. They get all
+	/// replicated components on the next update.
+	pub fn accept(&mut self) -> io::Result<usize> {
+		let mut accepted = 0;
+		loop {
+			match self.listener.accept() {
+				Ok((stream, _)) => {
+					stream.set_nonblocking(false)?;
+					stream.set_write_timeout(Some(self.write_timeout))?;
+					stream.set_nodelay(true)?;
+					self.joining.push(stream);
+					accepted += 1;
+				},
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(accepted),
+				Err(e) => return Err(e)
+			}
+		}
+	}
+
+	/// The number of connected clients, including the ones that have not been updated yet.
+	pub fn clients(&self) -> usize {
+		self.joining.len() + self.clients.len()
+	}
+
+	/// Accepts waiting clients and sends the changes since the last update. Empty deltas
+	/// are not sent, clients that can not be written to within the write timeout are
+	/// disconnected.
+	pub fn update(&mut self) -> io::Result<()> {
+		self.accept()?;
+
+		let delta = self.tracker.delta();
+		if !delta.is_empty() {
+			let bytes = codec::to_bytes(&delta);
+			self.clients.retain(|stream| write_frame(stream, &bytes).is_ok());
+		}
+
+		if !self.joining.is_empty() {
+			let bytes = codec::to_bytes(&Tracker::for_lists(&self.labels).delta());
+			for stream in self.joining.drain(..) {
+				if write_frame(&stream, &bytes).is_ok() {
+					self.clients.push(stream);
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub enum ReplicationError {
+	Io(io::Error),
+	Decode(DecodeError),
+	Delta(DeltaError),
+	/// A forwarded event is not marked as remote or has no codec.
+	UnknownEvent(String),
+	/// A frame is longer than `MAX_FRAME_LEN`.
+	FrameTooLong(usize),
+	/// The server closed the connection.
+	Closed
+}
+
+impl fmt::Display for ReplicationError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReplicationError::Io(ref error) => write!(f, "Replication connection failed: {}", error),
+			ReplicationError::Decode(ref error) => write!(f, "Could not decode replicated changes: {}", error),
+			ReplicationError::Delta(ref error) => write!(f, "Could not apply replicated changes: {}", error),
+			ReplicationError::UnknownEvent(ref name) => write!(f, "Forwarded event {} is not a remote event", name),
+			ReplicationError::FrameTooLong(len) => write!(f, "Received a frame of {} bytes, the maximum is {}", len, MAX_FRAME_LEN),
+			ReplicationError::Closed => write!(f, "The replication server closed the connection")
+		}
+	}
+}
+
+impl Error for ReplicationError {}
+
+impl From<io::Error> for ReplicationError {
+	fn from(error: io::Error) -> ReplicationError {
+		ReplicationError::Io(error)
+	}
+}
+
+/// A stream that frames are read from, the bytes of incomplete frames are kept.
+struct Connection {
+	stream: TcpStream,
+	buffer: Vec<u8>,
+	closed: bool
+}
+
+impl Connection {
+	fn new(stream: TcpStream) -> Connection {
+		Connection { stream: stream, buffer: vec![], closed: false }
+	}
+
+	/// Reads everything that has arrived, without blocking.
+	fn read_available(&mut self) -> io::Result<()> {
+		self.stream.set_nonblocking(true)?;
+		let mut chunk = [0u8; 4096];
+		while !self.closed {
+			match self.stream.read(&mut chunk) {
+				Ok(0) => self.closed = true,
+				Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+				Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+				Err(e) => return Err(e)
+			}
+		}
+		Ok(())
+	}
+
+	/// Waits for the next frame.
+	fn read_frame(&mut self) -> Result<Vec<u8>, ReplicationError> {
+		self.stream.set_nonblocking(false)?;
+		let mut chunk = [0u8; 4096];
+		loop {
+			if let Some(frame) = self.next_frame()? {
+				return Ok(frame);
+			}
+			if self.closed {
+				return Err(ReplicationError::Closed);
+			}
+
+			match self.stream.read(&mut chunk) {
+				Ok(0) => self.closed = true,
+				Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+				Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+				Err(e) => return Err(e.into())
+			}
+		}
+	}
+
+	/// Takes the next complete frame from the buffer. The length is checked as soon as
+	/// it arrives, so a peer can not make the buffer grow without limit.
+	fn next_frame(&mut self) -> Result<Option<Vec<u8>>, ReplicationError> {
+		if self.buffer.len() < 8 {
+			return Ok(None);
+		}
+		let len = usize::decode(&mut &self.buffer[..8]).map_err(ReplicationError::Decode)?;
+		let end = match len.checked_add(8) {
+			Some(end) if len <= MAX_FRAME_LEN => end,
+			_ => return Err(ReplicationError::FrameTooLong(len))
+		};
+		if self.buffer.len() < end {
+			return Ok(None);
+		}
+		let frame = self.buffer[8..end].to_vec();
+		self.buffer.drain(..end);
+		Ok(Some(frame))
+	}
+}
+
+/// Gives an event sender the tick of the receiving world.
+pub type TickSource = Arc<Fn() -> u64 + Send + Sync>;
+
+/// Receives changes from a server and applies them to this world.
+pub struct Client {
+	connection: Connection,
+	tick: Arc<AtomicU64>
+}
+
+impl Client {
+	pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Client> {
+		let stream = TcpStream::connect(address)?;
+		stream.set_nodelay(true)?;
+		Ok(Client { connection: Connection::new(stream), tick: Arc::new(AtomicU64::new(0)) })
+	}
+
+	/// The server tick of the last applied changes.
+	pub fn tick(&self) -> u64 {
+		self.tick.load(Ordering::SeqCst)
+	}
+
+	/// Reads the server tick of the last applied changes, for event senders.
+	pub fn tick_source(&self) -> TickSource {
+		let tick = self.tick.clone();
+		Arc::new(move || tick.load(Ordering::SeqCst))
+	}
+
+	/// Limits how long `receive` waits, `None` waits forever.
+	pub fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+		self.connection.stream.set_read_timeout(timeout)
+	}
+
+	/// Applies the changes that have arrived without waiting, returns how many deltas
+	/// were applied.
+	pub fn poll(&mut self) -> Result<usize, ReplicationError> {
+		self.connection.read_available()?;
+
+		let mut applied = 0;
+		while let Some(frame) = self.connection.next_frame()? {
+			self.apply(&frame)?;
+			applied += 1;
+		}
+		if applied == 0 && self.connection.closed {
+			return Err(ReplicationError::Closed);
+		}
+		Ok(applied)
+	}
+
+	/// Waits for the next changes and applies them.
+	pub fn receive(&mut self) -> Result<(), ReplicationError> {
+		let frame = self.connection.read_frame()?;
+		self.apply(&frame)
+	}
+
+	fn apply(&mut self, frame: &[u8]) -> Result<(), ReplicationError> {
+		let delta : Delta = codec::from_bytes(frame).map_err(ReplicationError::Decode)?;
+		delta.apply().map_err(ReplicationError::Delta)?;
+		self.tick.store(delta.to_tick, Ordering::SeqCst);
+		Ok(())
+	}
+}
+
+/// Marks the event as remote, so its triggers are forwarded by event senders and its
+/// data is accepted by event receivers. The codec of the event has to be registered
+/// first.
+pub fn forward_event<E: EventType>() {
+	assert!(events::codec(E::name()).is_some(), "Remote event {} has no codec registered", E::name());
+	let mut remote = REMOTE_EVENTS.write().expect("REMOTE_EVENTS lock corrupted");
+	remote.insert(E::name());
+}
+
+fn is_remote(event_name: &str) -> bool {
+	let remote = REMOTE_EVENTS.read().expect("REMOTE_EVENTS lock corrupted");
+	remote.contains(event_name)
+}
+
+/// A forwarded trigger, the data is delivered on the receiving end at the given tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteEvent {
+	pub tick: u64,
+	pub event_name: String,
+	pub data: Vec<u8>
+}
+
+codec!{ RemoteEvent { tick, event_name, data } }
+
+/// Forwards the external triggers of remote events to a receiver, like client input
+/// to the server. The triggers still run locally as well.
+pub struct EventSender {
+	stream: TcpStream,
+	observer: events::ObserverID,
+	outbox: Arc<SharedMutex<Vec<RemoteEvent>>>
+}
+
+impl EventSender {
+	/// Connects to a receiver. The data of every trigger is delivered `delay` ticks after
+	/// the tick of the receiver that the tick source gives when it is triggered, so it
+	/// can arrive in time.
+	pub fn connect<A: ToSocketAddrs>(address: A, delay: u64, tick_source: TickSource) -> io::Result<EventSender> {
+		let stream = TcpStream::connect(address)?;
+		stream.set_nodelay(true)?;
+
+		let outbox = Arc::new(SharedMutex::new(vec![]));
+		let observer_outbox = outbox.clone();
+		let observer = events::add_trigger_observer(move |trigger| {
+			if !trigger.external || !is_remote(trigger.event_name) {
+				return;
+			}
+
+			// forward_event checked that remote events have a codec
+			let event_codec = match events::codec(trigger.event_name) {
+				Some(event_codec) => event_codec,
+				None => return
+			};
+			let mut data = vec![];
+			(event_codec.encode)(trigger.data, &mut data);
+			let mut outbox = observer_outbox.write().expect("EventSender outbox corrupted");
+			outbox.push(RemoteEvent {
+				tick: tick_source() + trigger.delay + delay,
+				event_name: trigger.event_name.to_string(),
+				data: data
+			});
+		});
+
+		Ok(EventSender {
+			stream: stream,
+			observer: observer,
+			outbox: outbox
+		})
+	}
+
+	/// Sends the triggers since the last flush, returns how many were sent.
+	pub fn flush(&mut self) -> io::Result<usize> {
+		let remote_events : Vec<RemoteEvent> = {
+			let mut outbox = self.outbox.write().expect("EventSender outbox corrupted");
+			outbox.drain(..).collect()
+		};
+
+		for remote_event in remote_events.iter() {
+			write_frame(&self.stream, &codec::to_bytes(remote_event))?;
+		}
+		Ok(remote_events.len())
+	}
+
+	/// Stops forwarding, triggers that were not flushed are dropped. Dropping the sender
+	/// does the same.
+	pub fn stop(self) {
+		drop(self);
+	}
+}
+
+impl Drop for EventSender {
+	fn drop(&mut self) {
+		events::remove_trigger_observer(self.observer);
+	}
+}
+
+/// Accepts event senders and delivers the data they forward.
+pub struct EventReceiver {
+	listener: TcpListener,
+	senders: Vec<Connection>,
+	pending: Vec<RemoteEvent>,
+	rejected: Vec<ReplicationError>,
+	late: usize
+}
+
+impl EventReceiver {
+	pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<EventReceiver> {
+		let listener = TcpListener::bind(address)?;
+		listener.set_nonblocking(true)?;
+		Ok(EventReceiver { listener: listener, senders: vec![], pending: vec![], rejected: vec![], late: 0 })
+	}
+
+	pub fn local_addr(&self) -> io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+
+	/// The number of connected senders.
+	pub fn senders(&self) -> usize {
+		self.senders.len()
+	}
+
+	/// The number of received triggers that are not due yet.
+	pub fn pending(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// The number of triggers that were delivered after the tick they were sent for,
+	/// because they arrived too late. A larger sender delay avoids them.
+	pub fn late(&self) -> usize {
+		self.late
+	}
+
+	/// Takes the errors of the triggers that were rejected since the last call.
+	pub fn take_rejected(&mut self) -> Vec<ReplicationError> {
+		self.rejected.drain(..).collect()
+	}
+
+	/// Accepts waiting senders, reads what they forwarded and delivers the data that is
+	/// due this tick, or was due before, see `late`. Data for later ticks is kept until
+	/// then. Triggers that can not be decoded or are not of a remote event are rejected
+	/// without stopping the others, see `take_rejected`. Returns how many triggers were
+	/// delivered, it should be called between `events::next_tick` and `events::run_tick`.
+	/// Delivered triggers are observed as external ones, so recorders capture them.
+	pub fn poll(&mut self) -> Result<usize, ReplicationError> {
+		loop {
+			match self.listener.accept() {
+				Ok((stream, _)) => self.senders.push(Connection::new(stream)),
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+				Err(e) => return Err(e.into())
+			}
+		}
+
+		for sender in self.senders.iter_mut() {
+			if sender.read_available().is_err() {
+				sender.closed = true;
+			}
+			loop {
+				let frame = match sender.next_frame() {
+					Ok(Some(frame)) => frame,
+					Ok(None) => break,
+					Err(error) => {
+						// the frames that follow can not be found, so the sender is dropped
+						self.rejected.push(error);
+						sender.closed = true;
+						break;
+					}
+				};
+				match codec::from_bytes(&frame) {
+					Ok(remote_event) => self.pending.push(remote_event),
+					Err(error) => self.rejected.push(ReplicationError::Decode(error))
+				}
+			}
+		}
+		self.senders.retain(|sender| !sender.closed);
+
+		let tick = events::current_tick();
+		// the sort is stable so triggers of a tick keep the order they arrived in
+		self.pending.sort_by_key(|remote_event| remote_event.tick);
+		let due = self.pending.iter().take_while(|remote_event| remote_event.tick <= tick).count();
+
+		let mut delivered = 0;
+		for remote_event in self.pending.drain(..due) {
+			let event_codec = match events::codec(&remote_event.event_name) {
+				Some(event_codec) if is_remote(&remote_event.event_name) => event_codec,
+				_ => {
+					self.rejected.push(ReplicationError::UnknownEvent(remote_event.event_name));
+					continue;
+				}
+			};
+			let mut input = &remote_event.data[..];
+			match (event_codec.deliver)(&mut input) {
+				Ok(()) => {
+					delivered += 1;
+					if remote_event.tick < tick {
+						self.late += 1;
+					}
+				},
+				Err(error) => self.rejected.push(ReplicationError::Decode(error))
+			}
+		}
+		Ok(delivered)
+	}
+}
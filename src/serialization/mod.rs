@@ -0,0 +1,468 @@
+/// Serde support, enabled by the `serde` feature. Types that implement `Serialize`
+/// and `Deserialize` are written in the same binary format as the `codec` module, so
+/// they can be registered wherever a `Codec` type can:
+///
+/// component!{ #[serde] #[derive(Default)] position, x: i64, y: i64 }
+/// event!{ #[serde] damaged, amount: i64 }
+///
+/// components::register_serde::<position::List>();
+/// events::register_serde::<damaged::Event>();
+/// snapshot::add_serde_state("score", score.state.clone());
+///
+/// The `#[serde]` attribute makes the macros derive both traits, it can be put among
+/// the other struct attributes. Systems take it on their state:
+///
+/// state #[serde] { total: i64 } { total = 0 }
+///
+/// The format is not self describing, so values can only be read back as the type
+/// they were written as. Sequences and maps are written with their length first.
+use serde::ser::{ self, Serialize };
+use serde::de::{ self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor };
+use std::fmt::Display;
+use std::str;
+
+use codec::{ self, Codec, DecodeError, Encoding };
+
+impl ser::Error for DecodeError {
+	fn custom<T: Display>(msg: T) -> DecodeError {
+		DecodeError::Invalid(msg.to_string())
+	}
+}
+
+impl de::Error for DecodeError {
+	fn custom<T: Display>(msg: T) -> DecodeError {
+		DecodeError::Invalid(msg.to_string())
+	}
+}
+
+/// Appends the value to the buffer. Serialization only fails when the `Serialize`
+/// implementation of the value reports an error.
+pub fn encode<T: Serialize + ?Sized>(value: &T, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+	value.serialize(&mut Serializer { out: out })
+}
+
+/// Reads a value from the start of the input and advances the input past it.
+pub fn decode<T: DeserializeOwned>(input: &mut &[u8]) -> Result<T, DecodeError> {
+	let mut deserializer = Deserializer { input: input };
+	let value = T::deserialize(&mut deserializer)?;
+	*input = deserializer.input;
+	Ok(value)
+}
+
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, DecodeError> {
+	let mut out = vec![];
+	encode(value, &mut out)?;
+	Ok(out)
+}
+
+/// Decodes a value from a buffer, failing if the buffer is not used up completely.
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+	let mut input = bytes;
+	let value = decode(&mut input)?;
+	if !input.is_empty() {
+		return Err(DecodeError::Invalid(format!("{} trailing bytes", input.len())));
+	}
+	Ok(value)
+}
+
+/// Encodes values with their serde implementations, for the registration functions.
+pub struct SerdeEncoding;
+
+impl<T: Serialize + DeserializeOwned> Encoding<T> for SerdeEncoding {
+	fn encode(value: &T, out: &mut Vec<u8>) {
+		encode(value, out).expect("Could not serialize value");
+	}
+
+	fn decode(input: &mut &[u8]) -> Result<T, DecodeError> {
+		decode(input)
+	}
+}
+
+pub struct Serializer<'a> {
+	out: &'a mut Vec<u8>
+}
+
+/// Serializes the elements of sequences, maps, tuples and structs. When the length
+/// of a sequence is not known up front a placeholder is written and filled in at the end.
+pub struct Compound<'a: 'b, 'b> {
+	serializer: &'b mut Serializer<'a>,
+	length_at: Option<usize>,
+	count: usize
+}
+
+impl<'a, 'b> Compound<'a, 'b> {
+	fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), DecodeError> {
+		self.count += 1;
+		value.serialize(&mut *self.serializer)
+	}
+
+	fn finish(self) -> Result<(), DecodeError> {
+		if let Some(at) = self.length_at {
+			let mut length = vec![];
+			self.count.encode(&mut length);
+			self.serializer.out[at..at + length.len()].copy_from_slice(&length);
+		}
+		Ok(())
+	}
+}
+
+impl<'a, 'b> Serializer<'a> {
+	fn compound(&'b mut self, len: Option<usize>, prefixed: bool) -> Compound<'a, 'b> {
+		let mut length_at = None;
+		if prefixed {
+			match len {
+				Some(len) => len.encode(self.out),
+				None => {
+					length_at = Some(self.out.len());
+					0usize.encode(self.out);
+				}
+			}
+		}
+		Compound { serializer: self, length_at: length_at, count: 0 }
+	}
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
+	type Ok = ();
+	type Error = DecodeError;
+	type SerializeSeq = Compound<'a, 'b>;
+	type SerializeTuple = Compound<'a, 'b>;
+	type SerializeTupleStruct = Compound<'a, 'b>;
+	type SerializeTupleVariant = Compound<'a, 'b>;
+	type SerializeMap = Compound<'a, 'b>;
+	type SerializeStruct = Compound<'a, 'b>;
+	type SerializeStructVariant = Compound<'a, 'b>;
+
+	fn serialize_bool(self, v: bool) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_i8(self, v: i8) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_i16(self, v: i16) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_i32(self, v: i32) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_i64(self, v: i64) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_u8(self, v: u8) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_u16(self, v: u16) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_u32(self, v: u32) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_u64(self, v: u64) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_f32(self, v: f32) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_f64(self, v: f64) -> Result<(), DecodeError> { v.encode(self.out); Ok(()) }
+	fn serialize_char(self, v: char) -> Result<(), DecodeError> { (v as u32).encode(self.out); Ok(()) }
+
+	fn serialize_str(self, v: &str) -> Result<(), DecodeError> {
+		self.serialize_bytes(v.as_bytes())
+	}
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<(), DecodeError> {
+		v.len().encode(self.out);
+		self.out.extend_from_slice(v);
+		Ok(())
+	}
+
+	fn serialize_none(self) -> Result<(), DecodeError> { false.encode(self.out); Ok(()) }
+
+	fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), DecodeError> {
+		true.encode(self.out);
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<(), DecodeError> { Ok(()) }
+	fn serialize_unit_struct(self, _: &'static str) -> Result<(), DecodeError> { Ok(()) }
+
+	fn serialize_unit_variant(self, _: &'static str, index: u32, _: &'static str) -> Result<(), DecodeError> {
+		index.encode(self.out);
+		Ok(())
+	}
+
+	fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<(), DecodeError> {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _: &'static str, index: u32, _: &'static str, value: &T) -> Result<(), DecodeError> {
+		index.encode(self.out);
+		value.serialize(self)
+	}
+
+	fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a, 'b>, DecodeError> {
+		Ok(self.compound(len, true))
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<Compound<'a, 'b>, DecodeError> {
+		Ok(self.compound(Some(len), false))
+	}
+
+	fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Compound<'a, 'b>, DecodeError> {
+		Ok(self.compound(Some(len), false))
+	}
+
+	fn serialize_tuple_variant(self, _: &'static str, index: u32, _: &'static str, len: usize) -> Result<Compound<'a, 'b>, DecodeError> {
+		index.encode(self.out);
+		Ok(self.compound(Some(len), false))
+	}
+
+	fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a, 'b>, DecodeError> {
+		Ok(self.compound(len, true))
+	}
+
+	fn serialize_struct(self, _: &'static str, len: usize) -> Result<Compound<'a, 'b>, DecodeError> {
+		Ok(self.compound(Some(len), false))
+	}
+
+	fn serialize_struct_variant(self, _: &'static str, index: u32, _: &'static str, len: usize) -> Result<Compound<'a, 'b>, DecodeError> {
+		index.encode(self.out);
+		Ok(self.compound(Some(len), false))
+	}
+}
+
+impl<'a, 'b> ser::SerializeSeq for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), DecodeError> { self.element(value) }
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+impl<'a, 'b> ser::SerializeTuple for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), DecodeError> { self.element(value) }
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+impl<'a, 'b> ser::SerializeTupleStruct for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), DecodeError> { self.element(value) }
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+impl<'a, 'b> ser::SerializeTupleVariant for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), DecodeError> { self.element(value) }
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+impl<'a, 'b> ser::SerializeMap for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), DecodeError> { self.element(key) }
+
+	fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), DecodeError> {
+		value.serialize(&mut *self.serializer)
+	}
+
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+impl<'a, 'b> ser::SerializeStruct for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, _: &'static str, value: &T) -> Result<(), DecodeError> { self.element(value) }
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+impl<'a, 'b> ser::SerializeStructVariant for Compound<'a, 'b> {
+	type Ok = ();
+	type Error = DecodeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, _: &'static str, value: &T) -> Result<(), DecodeError> { self.element(value) }
+	fn end(self) -> Result<(), DecodeError> { self.finish() }
+}
+
+pub struct Deserializer<'de> {
+	input: &'de [u8]
+}
+
+impl<'de> Deserializer<'de> {
+	fn read<T: Codec>(&mut self) -> Result<T, DecodeError> {
+		T::decode(&mut self.input)
+	}
+
+	fn read_bytes(&mut self) -> Result<&'de [u8], DecodeError> {
+		let len = self.read::<usize>()?;
+		codec::take(&mut self.input, len)
+	}
+}
+
+/// Hands out a known number of elements, or of keys and values for maps.
+struct Elements<'a, 'de: 'a> {
+	deserializer: &'a mut Deserializer<'de>,
+	remaining: usize
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for Elements<'a, 'de> {
+	type Error = DecodeError;
+
+	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, DecodeError> {
+		if self.remaining == 0 {
+			return Ok(None);
+		}
+		self.remaining -= 1;
+		seed.deserialize(&mut *self.deserializer).map(Some)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.remaining)
+	}
+}
+
+impl<'a, 'de> de::MapAccess<'de> for Elements<'a, 'de> {
+	type Error = DecodeError;
+
+	fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, DecodeError> {
+		de::SeqAccess::next_element_seed(self, seed)
+	}
+
+	fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DecodeError> {
+		seed.deserialize(&mut *self.deserializer)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.remaining)
+	}
+}
+
+macro_rules! deserialize_numbers {
+	( $( $method:ident => $visit:ident : $typ:ty ),* ) => ( $(
+		fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+			visitor.$visit(self.read::<$typ>()?)
+		}
+	)* )
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+	type Error = DecodeError;
+
+	fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, DecodeError> {
+		Err(DecodeError::Invalid("The format is not self describing, the type has to be known".to_string()))
+	}
+
+	deserialize_numbers!{
+		deserialize_bool => visit_bool: bool,
+		deserialize_i8 => visit_i8: i8,
+		deserialize_i16 => visit_i16: i16,
+		deserialize_i32 => visit_i32: i32,
+		deserialize_i64 => visit_i64: i64,
+		deserialize_u8 => visit_u8: u8,
+		deserialize_u16 => visit_u16: u16,
+		deserialize_u32 => visit_u32: u32,
+		deserialize_u64 => visit_u64: u64,
+		deserialize_f32 => visit_f32: f32,
+		deserialize_f64 => visit_f64: f64
+	}
+
+	fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		let code = self.read::<u32>()?;
+		let c = ::std::char::from_u32(code).ok_or_else(|| DecodeError::Invalid(format!("{} is not a char", code)))?;
+		visitor.visit_char(c)
+	}
+
+	fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		let bytes = self.read_bytes()?;
+		visitor.visit_borrowed_str(str::from_utf8(bytes).map_err(|e| DecodeError::Invalid(e.to_string()))?)
+	}
+
+	fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		self.deserialize_str(visitor)
+	}
+
+	fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		visitor.visit_borrowed_bytes(self.read_bytes()?)
+	}
+
+	fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		self.deserialize_bytes(visitor)
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		if self.read::<bool>()? {
+			visitor.visit_some(self)
+		} else {
+			visitor.visit_none()
+		}
+	}
+
+	fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, DecodeError> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, DecodeError> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		let len = self.read::<usize>()?;
+		visitor.visit_seq(Elements { deserializer: self, remaining: len })
+	}
+
+	fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+		visitor.visit_seq(Elements { deserializer: self, remaining: len })
+	}
+
+	fn deserialize_tuple_struct<V: Visitor<'de>>(self, _: &'static str, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+		self.deserialize_tuple(len, visitor)
+	}
+
+	fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		let len = self.read::<usize>()?;
+		visitor.visit_map(Elements { deserializer: self, remaining: len })
+	}
+
+	fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, DecodeError> {
+		self.deserialize_tuple(fields.len(), visitor)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value, DecodeError> {
+		visitor.visit_enum(self)
+	}
+
+	fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		self.deserialize_u32(visitor)
+	}
+
+	fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+		self.deserialize_any(visitor)
+	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+	type Error = DecodeError;
+	type Variant = Self;
+
+	fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), DecodeError> {
+		let index = self.read::<u32>()?;
+		let variant = seed.deserialize(IntoDeserializer::<DecodeError>::into_deserializer(index))?;
+		Ok((variant, self))
+	}
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+	type Error = DecodeError;
+
+	fn unit_variant(self) -> Result<(), DecodeError> {
+		Ok(())
+	}
+
+	fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, DecodeError> {
+		seed.deserialize(self)
+	}
+
+	fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+		de::Deserializer::deserialize_tuple(self, len, visitor)
+	}
+
+	fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, DecodeError> {
+		de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+	}
+}
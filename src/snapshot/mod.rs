@@ -0,0 +1,262 @@
+/// Snapshots capture the state of the world so it can be saved and restored later,
+/// for example in a fresh process as a save game. A snapshot contains the current
+/// tick, the entity allocator, the timers clock and the due times of named timers, every component list that has a codec registered with
+/// `components::register_codec`, the queued data of every event that has a codec
+/// registered with `events::register_codec` and the system states added to the
+/// snapshot registry:
+///
+/// let score = score_system::register();
+/// snapshot::add_state("score", score.state.clone());
+///
+/// snapshot::take().save("world.sav")?;
+///
+/// Restoring replaces the saved lists, queues and states. The process restoring the
+/// snapshot has to register the same components, events and states first, and has to
+/// schedule its named timers again, see `timers::restore`.
+///
+/// Snapshot::load("world.sav")?.restore()?;
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{ self, Read, Write, BufReader, BufWriter };
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use shared_mutex::SharedMutex;
+
+use codec::{ self, Codec, CodecEncoding, DecodeAnyFn, DecodeError, Encoding };
+use components;
+use entities::{ self, EntityID };
+use events;
+use timers;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, de::DeserializeOwned };
+#[cfg(feature = "serde")]
+use serialization::SerdeEncoding;
+
+const MAGIC : &[u8] = b"ERSNAP";
+const VERSION : u32 = 3;
+
+struct StateEntry {
+	state: Arc<Any + Send + Sync>,
+	encode: fn(&Any, &mut Vec<u8>),
+	decode: DecodeAnyFn,
+	replace: fn(&Any, Box<Any>)
+}
+
+lazy_static! {
+	static ref STATES: SharedMutex<HashMap<String, StateEntry>> = SharedMutex::new(HashMap::new());
+}
+
+/// Adds a system state to the snapshots under the given name, replacing the state
+/// that was added under that name before.
+pub fn add_state<S>(name: &str, state: Arc<SharedMutex<S>>) where S: Codec + Any + Send + Sync {
+	add_encoded_state::<S, CodecEncoding>(name, state);
+}
+
+/// Adds a system state with serde implementations to the snapshots, like `add_state`.
+#[cfg(feature = "serde")]
+pub fn add_serde_state<S>(name: &str, state: Arc<SharedMutex<S>>) where S: Serialize + DeserializeOwned + Any + Send + Sync {
+	add_encoded_state::<S, SerdeEncoding>(name, state);
+}
+
+fn add_encoded_state<S, F>(name: &str, state: Arc<SharedMutex<S>>) where S: Any + Send + Sync, F: Encoding<S> {
+	let entry = StateEntry {
+		state: state,
+		encode: encode_state::<S, F>,
+		decode: decode_state::<S, F>,
+		replace: replace_state::<S>
+	};
+
+	let mut states = STATES.write().expect("STATES lock corrupted");
+	states.insert(name.to_string(), entry);
+}
+
+pub fn remove_state(name: &str) {
+	let mut states = STATES.write().expect("STATES lock corrupted");
+	states.remove(name);
+}
+
+fn encode_state<S: Any + Send + Sync, F: Encoding<S>>(state: &Any, out: &mut Vec<u8>) {
+	let state = state.downcast_ref::<SharedMutex<S>>().expect("State not of expected type.");
+	F::encode(&state.read().expect("State lock corrupted"), out);
+}
+
+fn decode_state<S: Any + Send + Sync, F: Encoding<S>>(input: &mut &[u8]) -> Result<Box<Any>, DecodeError> {
+	let decoded : S = F::decode(input)?;
+	Ok(Box::new(decoded))
+}
+
+fn replace_state<S: Any + Send + Sync>(state: &Any, decoded: Box<Any>) {
+	let state = state.downcast_ref::<SharedMutex<S>>().expect("State not of expected type.");
+	*state.write().expect("State lock corrupted") = *decoded.downcast::<S>().expect("Decoded state not of expected type.");
+}
+
+/// The labels of the registered components that have no codec, so are left out of
+/// snapshots.
+pub fn unsaved_components() -> Vec<&'static str> {
+	components::registered_labels().into_iter().filter(|label| components::codec(label).is_none()).collect()
+}
+
+/// Captures the state of the world. Handlers should not be running, the lists are
+/// locked one at a time.
+pub fn take() -> Snapshot {
+	let components = components::codec_labels().into_iter().map(|label| {
+		let list_codec = components::codec(label).expect("Component codec was removed");
+		let mut data = vec![];
+		(list_codec.encode)(&mut data);
+		(label.to_string(), data)
+	}).collect();
+
+	let events = events::codec_names().into_iter().map(|name| {
+		let event_codec = events::codec(&name).expect("Event codec was removed");
+		let mut data = vec![];
+		(event_codec.save_queues)(&mut data);
+		(name, data)
+	}).collect();
+
+	let states = {
+		let states = STATES.read().expect("STATES lock corrupted");
+		let mut names : Vec<&String> = states.keys().collect();
+		names.sort();
+		names.into_iter().map(|name| {
+			let entry = &states[name];
+			let mut data = vec![];
+			(entry.encode)(&*entry.state, &mut data);
+			(name.clone(), data)
+		}).collect()
+	};
+
+	Snapshot {
+		tick: events::current_tick(),
+		next_entity: entities::next_entity_id(),
+		clock: timers::now(),
+		timers: timers::due_times(),
+		components: components,
+		events: events,
+		states: states
+	}
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+	UnknownComponent(String),
+	UnknownEvent(String),
+	UnknownState(String),
+	UnknownTimer(String),
+	Decode(String, DecodeError)
+}
+
+impl fmt::Display for SnapshotError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SnapshotError::UnknownComponent(ref label) => write!(f, "Saved component {} has no codec registered", label),
+			SnapshotError::UnknownEvent(ref name) => write!(f, "Saved event {} has no codec registered", name),
+			SnapshotError::UnknownState(ref name) => write!(f, "Saved state {} was not added", name),
+			SnapshotError::UnknownTimer(ref name) => write!(f, "Saved timer {} is not scheduled", name),
+			SnapshotError::Decode(ref name, ref error) => write!(f, "Could not decode saved {}: {}", name, error)
+		}
+	}
+}
+
+impl Error for SnapshotError {}
+
+/// The saved state of the world, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+	pub tick: u64,
+	pub next_entity: EntityID,
+	pub clock: Duration,
+	/// The active named timers by name and when they are due.
+	pub timers: Vec<(String, Duration)>,
+	/// The encoded component lists by label.
+	pub components: Vec<(String, Vec<u8>)>,
+	/// The encoded event queues by event name.
+	pub events: Vec<(String, Vec<u8>)>,
+	/// The encoded system states by the name they were added under.
+	pub states: Vec<(String, Vec<u8>)>
+}
+
+codec!{ Snapshot { tick, next_entity, clock, timers, components, events, states } }
+
+impl Snapshot {
+	/// Replaces the state of the world with the snapshot. Everything in the snapshot is
+	/// decoded before anything is replaced, so nothing changes when restoring fails.
+	pub fn restore(&self) -> Result<(), SnapshotError> {
+		let mut lists = vec![];
+		for (label, data) in self.components.iter() {
+			let list_codec = components::codec(label).ok_or_else(|| SnapshotError::UnknownComponent(label.clone()))?;
+			let list = (list_codec.decode)(&mut &data[..]).map_err(|e| SnapshotError::Decode(label.clone(), e))?;
+			lists.push((list_codec, list));
+		}
+
+		let mut queues = vec![];
+		for (name, data) in self.events.iter() {
+			let event_codec = events::codec(name).ok_or_else(|| SnapshotError::UnknownEvent(name.clone()))?;
+			let queue = (event_codec.decode_queues)(&mut &data[..]).map_err(|e| SnapshotError::Decode(name.clone(), e))?;
+			queues.push((event_codec, queue));
+		}
+
+		let states = STATES.read().expect("STATES lock corrupted");
+		let mut decoded_states = vec![];
+		for (name, data) in self.states.iter() {
+			let entry = states.get(name).ok_or_else(|| SnapshotError::UnknownState(name.clone()))?;
+			let state = (entry.decode)(&mut &data[..]).map_err(|e| SnapshotError::Decode(name.clone(), e))?;
+			decoded_states.push((entry, state));
+		}
+
+		timers::restore(self.clock, &self.timers).map_err(SnapshotError::UnknownTimer)?;
+		events::set_current_tick(self.tick);
+		entities::set_next_entity_id(self.next_entity);
+
+		for (list_codec, list) in lists {
+			(list_codec.replace)(list);
+		}
+
+		for (event_codec, queue) in queues {
+			(event_codec.restore_queues)(queue);
+		}
+
+		for (entry, state) in decoded_states {
+			(entry.replace)(&*entry.state, state);
+		}
+		Ok(())
+	}
+
+	pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		let mut out = MAGIC.to_vec();
+		VERSION.encode(&mut out);
+		self.encode(&mut out);
+		writer.write_all(&out)
+	}
+
+	pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Snapshot> {
+		let mut bytes = vec![];
+		reader.read_to_end(&mut bytes)?;
+
+		if !bytes.starts_with(MAGIC) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a snapshot"));
+		}
+
+		let (version, snapshot) : (u32, Snapshot) = codec::from_bytes(&bytes[MAGIC.len()..])
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		if version != VERSION {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported snapshot version {}", version)));
+		}
+
+		Ok(snapshot)
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		let mut writer = BufWriter::new(File::create(path)?);
+		self.write_to(&mut writer)?;
+		writer.flush()
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Snapshot> {
+		let mut reader = BufReader::new(File::open(path)?);
+		Snapshot::read_from(&mut reader)
+	}
+}
@@ -0,0 +1,249 @@
+/// Aggregated timings of the event loop, only available with the `tracing` feature.
+///
+/// Once enabled the stats keep the run times of the last `window` invocations of
+/// every system and event, count the triggers of every event and keep a warning for
+/// every handler that takes longer than the configured budget.
+///
+/// stats::enable(stats::Config { window: 600, handler_budget: Some(Duration::from_millis(2)) });
+/// // .. run the simulation
+/// println!("{}", stats::report());
+use std::collections::{ BTreeMap, VecDeque };
+use std::fmt;
+use std::time::Duration;
+use shared_mutex::SharedMutex;
+
+use events;
+use tracing;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+	/// The number of most recent samples the timings are aggregated over.
+	pub window: usize,
+	/// Handlers that run longer than the budget are counted and the most recent
+	/// `window` of them are kept as warnings.
+	pub handler_budget: Option<Duration>
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			window: 1000,
+			handler_budget: None
+		}
+	}
+}
+
+/// The timings of the samples in the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Summary {
+	pub count: usize,
+	pub min: Duration,
+	pub avg: Duration,
+	pub p99: Duration,
+	pub max: Duration
+}
+
+impl Summary {
+	fn of(samples: &VecDeque<Duration>) -> Option<Summary> {
+		if samples.is_empty() {
+			return None;
+		}
+
+		let mut sorted : Vec<Duration> = samples.iter().cloned().collect();
+		sorted.sort();
+		let count = sorted.len();
+		let total = sorted.iter().fold(Duration::new(0, 0), |total, &d| total + d);
+		let p99_index = (count * 99).div_ceil(100) - 1;
+
+		Some(Summary {
+			count: count,
+			min: sorted[0],
+			avg: total / count as u32,
+			p99: sorted[p99_index],
+			max: sorted[count - 1]
+		})
+	}
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "n={} min={:?} avg={:?} p99={:?} max={:?}", self.count, self.min, self.avg, self.p99, self.max)
+	}
+}
+
+/// A handler invocation that took longer than the budget.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+	pub tick: u64,
+	pub system_name: String,
+	pub event_name: String,
+	pub run_time: Duration,
+	pub budget: Duration
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} took {:?} handling {} at tick {}, over its budget of {:?}",
+			self.system_name, self.run_time, self.event_name, self.tick, self.budget)
+	}
+}
+
+#[derive(Default)]
+struct Collected {
+	config: Config,
+	systems: BTreeMap<String, VecDeque<Duration>>,
+	events: BTreeMap<String, VecDeque<Duration>>,
+	triggers: BTreeMap<String, u64>,
+	slow_handlers: u64,
+	warnings: VecDeque<Warning>
+}
+
+fn push_sample(samples: &mut BTreeMap<String, VecDeque<Duration>>, name: &str, sample: Duration, window: usize) {
+	let window_samples = samples.entry(name.to_string()).or_default();
+	window_samples.push_back(sample);
+	while window_samples.len() > window {
+		window_samples.pop_front();
+	}
+}
+
+struct Collector;
+
+impl tracing::Subscriber for Collector {
+	fn handler(&self, span: &tracing::HandlerSpan) {
+		let mut collected = COLLECTED.write().expect("Stats mutex corrupted");
+		let config = collected.config;
+		push_sample(&mut collected.systems, span.system_name, span.run_time, config.window);
+
+		if let Some(budget) = config.handler_budget {
+			if span.run_time > budget {
+				collected.slow_handlers += 1;
+				collected.warnings.push_back(Warning {
+					tick: span.tick,
+					system_name: span.system_name.to_string(),
+					event_name: span.event_name.to_string(),
+					run_time: span.run_time,
+					budget: budget
+				});
+				while collected.warnings.len() > config.window {
+					collected.warnings.pop_front();
+				}
+			}
+		}
+	}
+
+	fn event_batch(&self, span: &tracing::BatchSpan) {
+		let mut collected = COLLECTED.write().expect("Stats mutex corrupted");
+		let window = collected.config.window;
+		push_sample(&mut collected.events, span.event_name, span.duration, window);
+	}
+}
+
+lazy_static! {
+	static ref COLLECTED: SharedMutex<Collected> = SharedMutex::new(Collected::default());
+	static ref ENABLED: SharedMutex<Option<(tracing::SubscriberID, events::ObserverID)>> = SharedMutex::new(None);
+}
+
+/// Starts collecting stats, enabling again only changes the configuration.
+pub fn enable(config: Config) {
+	COLLECTED.write().expect("Stats mutex corrupted").config = config;
+
+	let mut enabled = ENABLED.write().expect("Stats mutex corrupted");
+	if enabled.is_none() {
+		let subscriber = tracing::add_subscriber(Collector);
+		let observer = events::add_trigger_observer(|trigger| {
+			let mut collected = COLLECTED.write().expect("Stats mutex corrupted");
+			*collected.triggers.entry(trigger.event_name.to_string()).or_insert(0) += 1;
+		});
+		*enabled = Some((subscriber, observer));
+	}
+}
+
+pub fn disable() {
+	let mut enabled = ENABLED.write().expect("Stats mutex corrupted");
+	if let Some((subscriber, observer)) = enabled.take() {
+		tracing::remove_subscriber(subscriber);
+		events::remove_trigger_observer(observer);
+	}
+}
+
+/// Clears all collected samples and counts.
+pub fn reset() {
+	let mut collected = COLLECTED.write().expect("Stats mutex corrupted");
+	let config = collected.config;
+	*collected = Collected::default();
+	collected.config = config;
+}
+
+/// The run times of the handlers of a system, by its module path.
+pub fn system(system_name: &str) -> Option<Summary> {
+	let collected = COLLECTED.read().expect("Stats mutex corrupted");
+	collected.systems.get(system_name).and_then(Summary::of)
+}
+
+/// The time it took to run all handlers of an event, by its name.
+pub fn event(event_name: &str) -> Option<Summary> {
+	let collected = COLLECTED.read().expect("Stats mutex corrupted");
+	collected.events.get(event_name).and_then(Summary::of)
+}
+
+pub fn trigger_count(event_name: &str) -> u64 {
+	let collected = COLLECTED.read().expect("Stats mutex corrupted");
+	collected.triggers.get(event_name).cloned().unwrap_or(0)
+}
+
+/// The number of handler invocations that exceeded the budget.
+pub fn slow_handlers() -> u64 {
+	COLLECTED.read().expect("Stats mutex corrupted").slow_handlers
+}
+
+/// The most recent handler invocations that exceeded the budget, oldest first.
+pub fn warnings() -> Vec<Warning> {
+	let collected = COLLECTED.read().expect("Stats mutex corrupted");
+	collected.warnings.iter().cloned().collect()
+}
+
+pub struct Report {
+	pub systems: Vec<(String, Summary)>,
+	pub events: Vec<(String, Summary)>,
+	pub triggers: Vec<(String, u64)>,
+	pub slow_handlers: u64,
+	pub warnings: Vec<Warning>
+}
+
+/// A report of everything that was collected, sorted by name.
+pub fn report() -> Report {
+	let collected = COLLECTED.read().expect("Stats mutex corrupted");
+	let summaries = |samples: &BTreeMap<String, VecDeque<Duration>>| samples.iter()
+		.filter_map(|(name, window)| Summary::of(window).map(|summary| (name.clone(), summary)))
+		.collect();
+
+	Report {
+		systems: summaries(&collected.systems),
+		events: summaries(&collected.events),
+		triggers: collected.triggers.iter().map(|(name, &count)| (name.clone(), count)).collect(),
+		slow_handlers: collected.slow_handlers,
+		warnings: collected.warnings.iter().cloned().collect()
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Systems:")?;
+		for (name, summary) in self.systems.iter() {
+			writeln!(f, "  {}: {}", name, summary)?;
+		}
+		writeln!(f, "Events:")?;
+		for (name, summary) in self.events.iter() {
+			writeln!(f, "  {}: {}", name, summary)?;
+		}
+		writeln!(f, "Triggers:")?;
+		for &(ref name, count) in self.triggers.iter() {
+			writeln!(f, "  {}: {}", name, count)?;
+		}
+		write!(f, "Slow handlers: {}", self.slow_handlers)?;
+		for warning in self.warnings.iter() {
+			write!(f, "\n  {}", warning)?;
+		}
+		Ok(())
+	}
+}
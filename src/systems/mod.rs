@@ -32,7 +32,7 @@
 /// pub mod my_system {
 ///   struct State { i: i64 }
 ///   
-///   pub fn event_name(state: &mut State, data: &Vec<event_name::Data>, positions: &Vec<Position>, descriptions: &mut Vec<Description> ) {
+///   pub fn event_name(state: &mut State, data: &[event_name::Data], positions: &Vec<Position>, descriptions: &mut Vec<Description> ) {
 ///     let position = positions[0];
 ///     descriptions[0].set(format!("Position = {},{}", position.x, position.y));
 ///   }
@@ -64,13 +64,13 @@ macro_rules! system {
 	( $system_name:ident { $($contents:tt)* } ) => {
 		pub mod $system_name {
 			use std::any::Any;
-			use shared_mutex::{ SharedMutex };
+			use $crate::shared_mutex::{ SharedMutex };
 			#[allow(unused_imports)]
 			use $crate::entities::{ ComponentList };
-			use shared_mutex::{ MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
+			use $crate::shared_mutex::{ MappedSharedMutexReadGuard, MappedSharedMutexWriteGuard };
 			use std::sync::Arc;
 
-			system_contents!{ ( $($contents)* ) [ ] [ ] }
+			$crate::system_contents!{ ( $($contents)* ) [ ] [ ] }
 		}
 	} 
 }
@@ -93,9 +93,9 @@ macro_rules! system_contents {
 			$( $saved_sync_decl:tt ),*
 		]
 	) => (
-		on! { ($event_name, { $( $event_declaration_mut )*  }, { $( $event_declaration)* }  ) $_self , $_data => $event_body }
+		$crate::on! { ($event_name, { $( $event_declaration_mut )*  }, { $( $event_declaration)* }  ) $_self , $_data => $event_body }
 
-		system_contents!{ 
+		$crate::system_contents!{ 
 			( $($rest)* )
 			[ ( $event_name, { $( $event_declaration_mut)* }, {$( $event_declaration)* } ) $(, $saved_decl)* ]
 			[ $($saved_sync_decl),* ]
@@ -112,9 +112,9 @@ macro_rules! system_contents {
 			$( $saved_sync_decl:tt ),*
 		] 
 	) => (
-		on_sync! { ($event_name, $_self, $_data) $event_body }
+		$crate::on_sync! { ($event_name, $_self, $_data) $event_body }
 
-		system_contents!{ 
+		$crate::system_contents!{ 
 			( $($rest)* )
 			[ $($saved_decl),* ]
 			[ $event_name $(, $saved_sync_decl)* ]
@@ -124,16 +124,16 @@ macro_rules! system_contents {
 
 	(
 		(
-			state { $($state_declaration:tt)* } $state_initalizer:block $($rest:tt)*
+			state $(#[$($state_attr:tt)*])* { $($state_declaration:tt)* } $state_initalizer:block $($rest:tt)*
 		) [
 			$( $saved_decl:tt ),*
 		] [ 
 			$( $saved_sync_decl:tt ),*
 		]
 	) => (
-		state! { ($($state_declaration)*), $state_initalizer }
+		$crate::state! { $(#[$($state_attr)*])* ($($state_declaration)*), $state_initalizer }
 
-		system_contents!{
+		$crate::system_contents!{
 			( $($rest)* )
 			[ $( $saved_decl ),* ]
 			[ $(, $saved_sync_decl)* ]
@@ -151,7 +151,7 @@ macro_rules! system_contents {
 	) => (
 		$token_tree
 
-		system_contents!{
+		$crate::system_contents!{
 			( $($rest)* )
 			[ $( $saved_decl ),* ]
 			[ $(, $saved_sync_decl)* ]
@@ -163,7 +163,7 @@ macro_rules! system_contents {
 	(
 		() [ $( $event_declaration:tt ),* ] [ $( $sync_event_declaration:tt ),* ]
 	) => (
-		system_register!{ ($( $event_declaration ),*) ($( $sync_event_declaration ),*) }
+		$crate::system_register!{ ($( $event_declaration ),*) ($( $sync_event_declaration ),*) }
 	)
 }
 
@@ -171,20 +171,27 @@ macro_rules! system_contents {
 macro_rules! on {
 	( ($event_name:ident, { $( $mut_name:ident : $mut_typ:tt )* } , { $($name:ident : $typ:tt)* } ) 
 		$_self:ident, $_data:ident => $event_body:block ) => (
+		$crate::on!{ @guards ($event_name, { $( $mut_name : $mut_typ [] )* }, { $( $name : $typ )* }) $_self, $_data => $event_body }
+	);
+
+	// Every write guard is passed as `&` followed by the tokens in its brackets, the
+	// handlers of `#[system]` take them as `&mut`.
+	( @guards ($event_name:ident, { $( $mut_name:ident : $mut_typ:tt [$($mut_ref:tt)*] )* } , { $($name:ident : $typ:tt)* } )
+		$_self:ident, $_data:ident => $event_body:block ) => (
 
 		impl State {
 			#[allow(unused_variables)]
 			pub fn $event_name(&mut $_self,
-				$_data: &Vec<$event_name::Data>,
-				$( $name : &MappedSharedMutexReadGuard<ComponentList<append_path_component!($typ,Component)>>),*
-				$( $mut_name : &MappedSharedMutexWriteGuard<ComponentList<append_path_component!($mut_typ,Component)>> ),* ) $event_body
+				$_data: &[$event_name::Data],
+				$( $name : &MappedSharedMutexReadGuard<$crate::append_path_component!($typ,Storage)>, )*
+				$( $mut_name : & $($mut_ref)* MappedSharedMutexWriteGuard<$crate::append_path_component!($mut_typ,Storage)>, )* ) $event_body
 		}
 
 		#[allow(unused_variables)]
 		#[allow(unused_mut)]
 		pub fn $event_name(
 				state: Arc<Any>,
-				data: &Vec<$event_name::Data>,
+				data: &[$event_name::Data],
 				components: Vec<MappedSharedMutexReadGuard<Any>>,
 				mut_components: Vec<MappedSharedMutexWriteGuard<Any>>
 			) {
@@ -192,13 +199,13 @@ macro_rules! on {
 			let mut mut_components_iter = mut_components.into_iter();
 
 			$(
-				let $name : MappedSharedMutexReadGuard<ComponentList<append_path_component!($typ,Component)>> = components_iter
+				let $name : MappedSharedMutexReadGuard<$crate::append_path_component!($typ,Storage)> = components_iter
 					.next().expect("Event components list too short.")
 					.map(|v| v.downcast_ref().expect("Event component not of expected type."));
 			)*
 
 			$(
-				let $mut_name: MappedSharedMutexWriteGuard<ComponentList<append_path_component!($mut_typ,Component)>> = mut_components_iter
+				let mut $mut_name: MappedSharedMutexWriteGuard<$crate::append_path_component!($mut_typ,Storage)> = mut_components_iter
 					.next().expect("Event mut_components list too short.")
 					.map(|v| v.downcast_mut().expect("Event component not of expected type."));
 			)*
@@ -207,8 +214,8 @@ macro_rules! on {
 
 			state_casted.write().expect("Event state corrupted").$event_name(
 				data,
-				$(&$name),*
-				$(&$mut_name),*
+				$(&$name,)*
+				$(& $($mut_ref)* $mut_name,)*
 			);
 		}
 	)
@@ -240,7 +247,10 @@ macro_rules! on_sync {
 
 #[macro_export]
 macro_rules! state {
-	( ($( $name:ident : $field:ty ),*), $initializer:block ) => (
+	( @attributes [$($attr:tt)*] [$($item:tt)*] [] ($( $name:ident : $field:ty ),*), $initializer:block ) => (
+		$($item)*
+
+		$($attr)*
 		pub struct State {
 			$(pub $name : $field),*
 		}
@@ -253,6 +263,10 @@ macro_rules! state {
 				$($name: $name),*
 			}
 		}
+	);
+
+	( $($body:tt)* ) => (
+		$crate::struct_attributes!{ state [] [] [] $($body)* }
 	)
 }
 
@@ -277,23 +291,57 @@ macro_rules! system_register {
 	)
 
 	) => (
-		pub fn register() {
+		/// A registered instance of this system. Every instance has its own state and
+		/// its own set of handlers, so a system can be registered multiple times.
+		pub struct Instance {
+			pub id: $crate::systems::InstanceID,
+			pub state: Arc<SharedMutex<State>>
+		}
+
+		pub fn register() -> Instance {
+			register_with_state(default_state())
+		}
+
+		pub fn register_with_state(state: State) -> Instance {
 			#[allow(unused_imports)]
 			use std::any::TypeId;
 
-			let state = default_state();
-
+			let id = $crate::systems::next_instance_id();
 			let state = Arc::new(SharedMutex::new(state));
 
 			$(
 				let mut_ts = vec![ $( TypeId::of::< $mut_typ::Component >() ),* ];
 				let ts = vec![ $( TypeId::of::< $typ::Component >() ),* ];
-				$event_name::register_handler(state.clone(), $event_name, ts, mut_ts);
+				$event_name::register_handler(id, module_path!(), state.clone(), $event_name, ts, mut_ts);
 			)*
 
 			$(
-				$sync_event_name::register_handler(state.clone(), $sync_event_name);
+				$sync_event_name::register_handler(id, state.clone(), $sync_event_name);
+			)*
+
+			Instance { id: id, state: state }
+		}
+
+		#[allow(unused_variables)]
+		pub fn unregister(instance: &Instance) {
+			$(
+				$event_name::unregister_handlers(instance.id);
+			)*
+
+			$(
+				$sync_event_name::unregister_handlers(instance.id);
 			)*
 		}
 	)
 }
+
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+/// Identifies a single registered instance of a system.
+pub type InstanceID = usize;
+
+static NEXT_INSTANCE_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn next_instance_id() -> InstanceID {
+	NEXT_INSTANCE_ID.fetch_add(1, Ordering::SeqCst)
+}
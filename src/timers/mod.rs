@@ -0,0 +1,183 @@
+/// Timers fire a callback after a delay or repeatedly at an interval, usually to
+/// trigger an event. They are driven by the step of the `tick` event, so they
+/// behave the same when ticks are stepped manually as when the ticker runs.
+///
+/// let fuse = timers::after(Duration::from_secs(3), || explode::trigger(entity));
+/// let autosave = timers::every(Duration::from_secs(300), || autosave::trigger());
+/// fuse.cancel();
+///
+/// Timers that are due in the same step fire in the order of their due time, timers
+/// with the same due time fire in the order they were scheduled.
+///
+/// Snapshots save the due times of named timers, a process that restores a snapshot
+/// schedules its named timers again before restoring:
+///
+/// timers::every(Duration::from_secs(300), || autosave::trigger()).named("autosave");
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration;
+use shared_mutex::SharedMutex;
+
+use events;
+
+pub type TimerID = usize;
+
+pub type TimerFn = Arc<Fn() + Send + Sync>;
+
+struct Timer {
+	id: TimerID,
+	name: Option<String>,
+	due: Duration,
+	interval: Option<Duration>,
+	fire: TimerFn
+}
+
+/// Handle to a scheduled timer that can be used to cancel it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimerHandle {
+	pub id: TimerID
+}
+
+impl TimerHandle {
+	/// Cancels the timer, returns false if it had already fired or was cancelled.
+	pub fn cancel(&self) -> bool {
+		let mut timers = TIMERS.write().expect("TIMERS mutex corrupted");
+		let count = timers.len();
+		timers.retain(|t| t.id != self.id);
+		timers.len() != count
+	}
+
+	/// Names the timer so snapshots can save and restore its due time, the names of
+	/// active timers have to be unique.
+	pub fn named(self, name: &str) -> TimerHandle {
+		let mut timers = TIMERS.write().expect("TIMERS mutex corrupted");
+		if timers.iter().any(|t| t.id != self.id && t.name.as_deref() == Some(name)) {
+			panic!("Two active timers are named {}", name);
+		}
+		if let Some(timer) = timers.iter_mut().find(|t| t.id == self.id) {
+			timer.name = Some(name.to_string());
+		}
+		self
+	}
+
+	pub fn is_active(&self) -> bool {
+		let timers = TIMERS.read().expect("TIMERS mutex corrupted");
+		timers.iter().any(|t| t.id == self.id)
+	}
+}
+
+lazy_static! {
+	static ref CLOCK: SharedMutex<Duration> = SharedMutex::new(Duration::new(0, 0));
+	static ref TIMERS: SharedMutex<Vec<Timer>> = SharedMutex::new(vec![]);
+}
+
+static NEXT_TIMER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Fires once after the given delay.
+pub fn after<F>(delay: Duration, fire: F) -> TimerHandle where F: Fn() + Send + Sync + 'static {
+	schedule(delay, None, Arc::new(fire))
+}
+
+/// Fires every interval, starting one interval from now.
+pub fn every<F>(interval: Duration, fire: F) -> TimerHandle where F: Fn() + Send + Sync + 'static {
+	assert!(interval > Duration::new(0, 0), "Repeating timers need an interval larger than zero.");
+	schedule(interval, Some(interval), Arc::new(fire))
+}
+
+fn schedule(delay: Duration, interval: Option<Duration>, fire: TimerFn) -> TimerHandle {
+	let id = NEXT_TIMER_ID.fetch_add(1, Ordering::SeqCst);
+	let now = *CLOCK.read().expect("CLOCK mutex corrupted");
+
+	let mut timers = TIMERS.write().expect("TIMERS mutex corrupted");
+	timers.push(Timer {
+		id: id,
+		name: None,
+		due: now + delay,
+		interval: interval,
+		fire: fire
+	});
+
+	TimerHandle { id: id }
+}
+
+/// The time that has passed according to the timers.
+pub fn now() -> Duration {
+	*CLOCK.read().expect("CLOCK mutex corrupted")
+}
+
+/// Moves the timers clock forward by a step and fires every timer that became due.
+/// A repeating timer fires once for every interval that fits in the step.
+pub fn advance(step: Duration) {
+	let now;
+	{
+		let mut clock = CLOCK.write().expect("CLOCK mutex corrupted");
+		*clock += step;
+		now = *clock;
+	}
+
+	loop {
+		let fire : TimerFn;
+		{
+			let mut timers = TIMERS.write().expect("TIMERS mutex corrupted");
+			let next = timers.iter().enumerate()
+				.filter(|&(_, t)| t.due <= now)
+				.min_by_key(|&(_, t)| (t.due, t.id))
+				.map(|(i, _)| i);
+
+			let index = match next {
+				Some(index) => index,
+				None => break
+			};
+
+			fire = timers[index].fire.clone();
+			match timers[index].interval {
+				Some(interval) => timers[index].due += interval,
+				None => { timers.remove(index); }
+			}
+		}
+
+		// the lock is released so the timer can schedule or cancel timers, its triggers
+		// are internal because the timer fires again when the simulation is replayed
+		let _internal = events::InternalTriggers::start();
+		fire();
+	}
+}
+
+/// The active named timers and when they are due, sorted by name.
+pub fn due_times() -> Vec<(String, Duration)> {
+	let timers = TIMERS.read().expect("TIMERS mutex corrupted");
+	let mut due : Vec<(String, Duration)> = timers.iter()
+		.filter_map(|t| t.name.clone().map(|name| (name, t.due)))
+		.collect();
+	due.sort();
+	due
+}
+
+/// Sets the clock and the due times returned by `due_times`, as snapshots do. The
+/// callbacks of timers can not be saved, so timers are matched by their name, the
+/// timers that are not in `due` keep the time they had left. Returns the first name
+/// in `due` that no active timer has, nothing changes then.
+pub fn restore(clock: Duration, due: &[(String, Duration)]) -> Result<(), String> {
+	let mut timers = TIMERS.write().expect("TIMERS mutex corrupted");
+	if let Some((name, _)) = due.iter().find(|(name, _)| !timers.iter().any(|t| t.name.as_ref() == Some(name))) {
+		return Err(name.clone());
+	}
+
+	let mut now = CLOCK.write().expect("CLOCK mutex corrupted");
+	for timer in timers.iter_mut() {
+		let saved = timer.name.as_ref().and_then(|name| due.iter().find(|(n, _)| n == name));
+		timer.due = match saved {
+			Some(&(_, due)) => due,
+			None => clock + timer.due.checked_sub(*now).unwrap_or_default()
+		};
+	}
+	*now = clock;
+	Ok(())
+}
+
+/// Cancels all timers and resets the clock.
+pub fn clear() {
+	let mut timers = TIMERS.write().expect("TIMERS mutex corrupted");
+	timers.clear();
+	*CLOCK.write().expect("CLOCK mutex corrupted") = Duration::new(0, 0);
+}
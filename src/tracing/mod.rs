@@ -0,0 +1,98 @@
+/// Instrumentation of the event loop, only available with the `tracing` feature.
+///
+/// Subscribers receive a span for every handler invocation, for every event that
+/// was run and for every tick. The spans of a tick arrive in the order they finish,
+/// so the handler spans of an event come before the span of the event itself.
+///
+/// struct Printer;
+///
+/// impl tracing::Subscriber for Printer {
+///   fn handler(&self, span: &tracing::HandlerSpan) {
+///     println!("{} handled {} in {:?}", span.system_name, span.event_name, span.run_time);
+///   }
+/// }
+///
+/// tracing::add_subscriber(Printer);
+use std::sync::atomic::{ AtomicBool, AtomicUsize, Ordering };
+use std::time::Duration;
+use shared_mutex::SharedMutex;
+
+/// A single invocation of an event handler.
+pub struct HandlerSpan<'a> {
+	pub tick: u64,
+	pub system_name: &'a str,
+	pub event_name: &'a str,
+	pub data_count: usize,
+	/// The time spent waiting for the component locks.
+	pub lock_wait: Duration,
+	pub run_time: Duration
+}
+
+/// All handler invocations of a single event.
+pub struct BatchSpan<'a> {
+	pub tick: u64,
+	pub event_name: &'a str,
+	pub handlers: usize,
+	pub duration: Duration
+}
+
+/// All events that were run by `events::run_tick`.
+pub struct TickSpan {
+	pub tick: u64,
+	/// The number of triggers that were run.
+	pub events: usize,
+	pub duration: Duration
+}
+
+#[allow(unused_variables)]
+pub trait Subscriber: Send + Sync {
+	fn handler(&self, span: &HandlerSpan) {}
+	fn event_batch(&self, span: &BatchSpan) {}
+	fn tick(&self, span: &TickSpan) {}
+}
+
+pub type SubscriberID = usize;
+
+lazy_static! {
+	static ref SUBSCRIBERS: SharedMutex<Vec<(SubscriberID, Box<Subscriber>)>> = SharedMutex::new(vec![]);
+}
+
+static NEXT_SUBSCRIBER_ID: AtomicUsize = AtomicUsize::new(0);
+static SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+
+pub fn add_subscriber<S>(subscriber: S) -> SubscriberID where S: Subscriber + 'static {
+	let id = NEXT_SUBSCRIBER_ID.fetch_add(1, Ordering::SeqCst);
+	let mut subscribers = SUBSCRIBERS.write().expect("SUBSCRIBERS mutex corrupted");
+	subscribers.push((id, Box::new(subscriber)));
+	SUBSCRIBED.store(true, Ordering::SeqCst);
+	id
+}
+
+pub fn remove_subscriber(id: SubscriberID) {
+	let mut subscribers = SUBSCRIBERS.write().expect("SUBSCRIBERS mutex corrupted");
+	subscribers.retain(|&(subscriber_id, _)| subscriber_id != id);
+	SUBSCRIBED.store(!subscribers.is_empty(), Ordering::SeqCst);
+}
+
+fn emit<F>(f: F) where F: Fn(&Subscriber) {
+	if !SUBSCRIBED.load(Ordering::SeqCst) {
+		return;
+	}
+
+	let subscribers = SUBSCRIBERS.read().expect("SUBSCRIBERS mutex corrupted");
+	for (_, subscriber) in subscribers.iter() {
+		f(&**subscriber);
+	}
+}
+
+pub fn handler(span: &HandlerSpan) {
+	emit(|s| s.handler(span));
+}
+
+pub fn event_batch(span: &BatchSpan) {
+	emit(|s| s.event_batch(span));
+}
+
+pub fn tick(span: &TickSpan) {
+	emit(|s| s.tick(span));
+}
@@ -0,0 +1,68 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::events;
+use std::sync::Mutex;
+
+event!{ echo, depth: i64 }
+event!{ burst, wave: i64 }
+
+// the limits are global, so the tests take turns
+static LIMITS: Mutex<()> = Mutex::new(());
+
+system!( echo_system {
+	use super::echo;
+
+	state { } { }
+
+	on echo, {}, {}, (self, data) => {
+		echo::trigger(data[0].depth + 1);
+	}
+});
+
+system!( burst_system {
+	use super::burst;
+
+	state { } { }
+
+	on burst, {}, {}, (self, data) => {
+		for _ in 0..10 {
+			burst::trigger(data[0].wave + 1);
+		}
+	}
+});
+
+#[test]
+fn run_tick_stops_infinite_cascades() {
+	let _limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+	events::set_limits(events::Limits { max_cascade_depth: 8, max_events_per_tick: 100 });
+	let instance = echo_system::register();
+
+	echo::trigger(0);
+	let error = events::run_tick().expect_err("Cascade was not stopped");
+	assert_eq!(error.limit, events::Limit::CascadeDepth(8));
+	assert_eq!(error.chain.len(), 8);
+	assert!(error.chain.iter().all(|names| names.iter().any(|name| name == echo::EVENT_NAME)));
+
+	echo_system::unregister(&instance);
+	assert!(events::run_tick().is_ok());
+	events::set_limits(events::Limits::default());
+}
+
+#[test]
+fn run_tick_counts_every_trigger() {
+	let _limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+	events::set_limits(events::Limits { max_cascade_depth: 64, max_events_per_tick: 25 });
+	let instance = burst_system::register();
+
+	// 1 + 10 + 10 triggers fit in the limit, the fourth wave exceeds it
+	burst::trigger(0);
+	let error = events::run_tick().expect_err("Triggers were not limited");
+	assert_eq!(error.limit, events::Limit::EventsPerTick(25));
+	assert_eq!(error.chain.len(), 4);
+
+	burst_system::unregister(&instance);
+	events::next_tick();
+	assert!(events::run_tick().is_ok());
+	events::set_limits(events::Limits::default());
+}
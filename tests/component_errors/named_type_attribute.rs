@@ -0,0 +1,8 @@
+#[macro_use]
+extern crate entity_rust;
+
+pub struct Health(pub u32);
+
+component! { #[derive(Debug)] health: super::Health }
+
+fn main() {}
@@ -1,10 +1,31 @@
 #[macro_use]
 extern crate entity_rust;
-#[macro_use]
-extern crate lazy_static;
-extern crate shared_mutex;
+extern crate trybuild;
 
 component! { test_component, a: i64, b: i64 }
+component! { other_component, c: i64 }
+component! {
+	#[derive(Debug, Clone, Copy, PartialEq)]
+	velocity,
+	#[doc="Units per tick."]
+	x: f32,
+	y: f32
+}
+component! { #[doc="Hit points left."] vigour, points: u32 }
+component! { heading: (f32, f32) }
+component! {
+	/// Whether a unit stands, crouches or lies down.
+	stance: super::Stance
+}
+component! { health: super::Health<u32> }
+
+#[derive(Debug, PartialEq)]
+pub enum Stance {
+	Standing,
+	Prone
+}
+
+pub struct Health<T>(pub T);
 
 fn reset_state() {
 	let mut state = test_component::LIST.write().expect("Component lock corrupted.");
@@ -29,6 +50,7 @@ fn generates_functions() {
 }
 
 use entity_rust::components;
+use entity_rust::entities::ComponentList;
 use std::any::{ TypeId };
 
 #[test]
@@ -44,3 +66,65 @@ fn gettable_lock() {
 	);
 	test_component::add_with_lock(components, 1, 2, 3);
 }
+
+#[test]
+fn locks_are_returned_in_requested_order() {
+	test_component::register();
+	other_component::register();
+	let test_type = TypeId::of::<test_component::Component>();
+	let other_type = TypeId::of::<other_component::Component>();
+
+	for &(read, write) in [(test_type, other_type), (other_type, test_type)].iter() {
+		let (locks, mut_locks) = components::get_components_locks(&[read], &[write]);
+		assert_eq!(locks.len(), 1);
+		assert_eq!(mut_locks.len(), 1);
+		let read_is_test = locks[0].downcast_ref::<ComponentList<test_component::Component>>().is_some();
+		let write_is_other = mut_locks[0].downcast_ref::<ComponentList<other_component::Component>>().is_some();
+		assert_eq!(read_is_test, read == test_type);
+		assert_eq!(write_is_other, write == other_type);
+	}
+}
+
+#[test]
+fn conflicting_component_types_are_detected() {
+	let test_type = TypeId::of::<test_component::Component>();
+	let other_type = TypeId::of::<other_component::Component>();
+
+	assert_eq!(components::check_component_types(&[test_type], &[other_type]), Ok(()));
+	assert_eq!(components::check_component_types(&[test_type, test_type], &[]), Err(test_type));
+	assert_eq!(components::check_component_types(&[test_type], &[test_type]), Err(test_type));
+	assert_eq!(components::check_component_types(&[], &[other_type, other_type]), Err(other_type));
+}
+
+#[test]
+fn component_struct_takes_attributes() {
+	let velocity = velocity::Component { x: 1.0, y: 0.5 };
+	let copy = velocity;
+	assert_eq!(copy, velocity);
+	assert_eq!(format!("{:?}", velocity), "Component { x: 1.0, y: 0.5 }");
+}
+
+#[test]
+fn struct_attributes_keep_default() {
+	let vigour : vigour::Component = Default::default();
+	assert_eq!(vigour.points, 0);
+}
+
+#[test]
+fn existing_types_are_components() {
+	heading::add(1, (0.0, 1.0));
+	stance::add(1, Stance::Prone);
+	health::add(1, Health(30));
+	health::register();
+
+	let stances = stance::LIST.read().expect("Component lock corrupted.");
+	assert_eq!(stances[0], (1, Stance::Prone));
+	assert_eq!((health::LIST.read().expect("Component lock corrupted.")[0].1).0, 30);
+	assert!(components::COMPONENTS.read().expect("COMPONENTS lock corrupted").contains_key(&TypeId::of::<Health<u32>>()));
+}
+
+#[test]
+fn named_types_only_take_doc_attributes() {
+	let cases = trybuild::TestCases::new();
+	cases.compile_fail("tests/component_errors/*.rs");
+}
@@ -0,0 +1,160 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::{ codec, components, entities, events };
+use entity_rust::codec::Codec;
+use entity_rust::delta::{ Delta, DeltaError, ListDelta, Tracker };
+use entity_rust::entities::{ EntityBuilder, EntityID };
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+component! { #[derive(Debug, Clone, PartialEq, Default)] position, x: i64, y: i64 }
+tag! { selected }
+relation! { guards }
+
+component! { #[derive(Debug, Clone, PartialEq, Default)] rank, level: u32 }
+
+codec!{ position::Component { x, y } }
+codec!{ rank::Component { level } }
+
+// trackers see every list with a codec, so the tests take turns
+static WORLD: Mutex<()> = Mutex::new(());
+
+static POSITIONS_ADDED: AtomicUsize = AtomicUsize::new(0);
+
+type World = (Vec<(EntityID, position::Component)>, Vec<EntityID>, Vec<(EntityID, EntityID)>);
+
+fn world() -> World {
+	let mut positions = position::LIST.read().expect("COMPONENT_LIST corrupted").clone();
+	positions.sort_by_key(|&(e, _)| e);
+	let selected = selected::LIST.read().expect("COMPONENT_LIST corrupted").iter().cloned().collect();
+	let guards = guards::LIST.read().expect("COMPONENT_LIST corrupted").iter().collect();
+	(positions, selected, guards)
+}
+
+fn clear_world() {
+	position::LIST.write().expect("COMPONENT_LIST corrupted").clear();
+	selected::LIST.write().expect("COMPONENT_LIST corrupted").clear();
+	*guards::LIST.write().expect("COMPONENT_LIST corrupted") = Default::default();
+}
+
+#[test]
+fn deltas_rebuild_the_world() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	position::register();
+	selected::register();
+	guards::register();
+	components::register_codec::<position::List>();
+	components::register_codec::<selected::List>();
+	components::register_codec::<guards::List>();
+
+	let mut tracker = Tracker::new();
+	let king = EntityBuilder::new().with(position::Component { x: 0, y: 0 }).spawn();
+	let knight = EntityBuilder::new()
+		.with(position::Component { x: 1, y: 0 })
+		.tag::<selected::Component>()
+		.spawn();
+	let squire = EntityBuilder::new().with(position::Component { x: 2, y: 0 }).spawn();
+	guards::add(knight, king);
+
+	let first = tracker.delta();
+	assert_eq!(first.spawned, vec![king, knight, squire]);
+	assert!(tracker.delta().is_empty());
+
+	position::write().iter_mut()
+		.filter(|&&mut (e, _)| e == knight)
+		.for_each(|&mut (_, ref mut p)| p.y = 5);
+	selected::remove(knight);
+	entities::despawn(squire);
+
+	let second = tracker.delta();
+	assert_eq!(second.despawned, vec![squire]);
+	assert!(second.spawned.is_empty());
+	let changed_positions = second.lists.iter().find(|l| l.label == position::LABEL).expect("Positions did not change");
+	assert_eq!(changed_positions.changed.iter().map(|&(e, _)| e).collect::<Vec<EntityID>>(), vec![knight]);
+	assert_eq!(changed_positions.removed, vec![squire]);
+
+	let expected = world();
+	let encoded : Vec<Vec<u8>> = vec![codec::to_bytes(&first), codec::to_bytes(&second)];
+	clear_world();
+	position::on_added(|_| { POSITIONS_ADDED.fetch_add(1, Ordering::SeqCst); });
+
+	for bytes in encoded.iter() {
+		codec::from_bytes::<Delta>(bytes).expect("Could not decode delta").apply().expect("Could not apply delta");
+	}
+	assert_eq!(world(), expected);
+	assert_eq!(POSITIONS_ADDED.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn only_written_lists_are_compared() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	rank::register();
+	components::register_codec::<rank::List>();
+
+	let sergeant = EntityBuilder::new().with(rank::Component { level: 3 }).spawn();
+	let mut tracker = Tracker::for_lists(&[rank::LABEL]);
+	assert_eq!(tracker.delta().spawned, vec![sergeant]);
+
+	// the list was written in the tick of the last delta, so it is compared once more
+	events::next_tick();
+	assert!(tracker.delta().is_empty());
+
+	// a direct write is not seen until it is marked
+	rank::LIST.write().expect("COMPONENT_LIST corrupted")[0].1.level = 4;
+	assert!(!components::changed_since(tracker.tick()).contains(&rank::LABEL));
+	assert!(tracker.delta().is_empty());
+
+	components::mark_written(std::any::TypeId::of::<rank::Component>());
+	assert!(components::changed_since(tracker.tick()).contains(&rank::LABEL));
+	let promoted = tracker.delta();
+	assert_eq!(promoted.lists.len(), 1);
+	assert_eq!(promoted.lists[0].changed.iter().map(|&(e, _)| e).collect::<Vec<EntityID>>(), vec![sergeant]);
+	rank::remove(sergeant);
+}
+
+#[test]
+fn despawns_only_mark_the_lists_of_the_entity() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	rank::register();
+	selected::register();
+	guards::register();
+
+	let private = EntityBuilder::new().tag::<selected::Component>().spawn();
+	events::next_tick();
+	let tick = events::current_tick();
+	entities::despawn(private);
+
+	let changed = components::changed_since(tick);
+	assert!(changed.contains(&selected::LABEL));
+	assert!(!changed.contains(&rank::LABEL));
+	assert!(!changed.contains(&guards::LABEL));
+}
+
+#[test]
+fn failed_apply_changes_nothing() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	rank::register();
+	components::register_codec::<rank::List>();
+
+	let recruit = entities::allocate();
+	let deserter = entities::allocate();
+	let mut valid = vec![];
+	1usize.encode(&mut valid);
+	rank::Component { level: 1 }.encode(&mut valid);
+
+	let delta = Delta {
+		spawned: vec![recruit, deserter],
+		lists: vec![ListDelta {
+			label: rank::LABEL.to_string(),
+			added: vec![(recruit, valid), (deserter, vec![1])],
+			..ListDelta::default()
+		}],
+		..Delta::default()
+	};
+	match delta.apply() {
+		Err(DeltaError::Decode(ref label, _)) if label == rank::LABEL => (),
+		other => panic!("Unexpected result {:?}", other)
+	}
+	assert!(rank::LIST.read().expect("COMPONENT_LIST corrupted").iter().all(|&(e, _)| e != recruit));
+}
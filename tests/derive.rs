@@ -0,0 +1,109 @@
+#[macro_use]
+extern crate entity_rust;
+extern crate entity_rust_derive;
+extern crate trybuild;
+
+use entity_rust_derive::{ Component, event, system };
+use entity_rust::events;
+use std::sync::Mutex;
+
+#[derive(Component, Default)]
+pub struct RigidBody {
+	pub mass: i64,
+	pub speed: i64
+}
+
+#[derive(Component)]
+pub enum Team {
+	Red,
+	Blue
+}
+
+#[event]
+pub struct Push {
+	pub force: i64
+}
+
+#[event(coalesce = Latest)]
+pub struct Aim {
+	pub x: i64,
+	pub y: i64
+}
+
+sync_event! { inspect, total: &'a mut i64 }
+
+// running events drains the queues of every event, so the tests take turns
+static EVENTS: Mutex<()> = Mutex::new(());
+
+#[system]
+pub mod physics {
+	use super::{ push, inspect, rigid_body, team };
+
+	pub struct State {
+		pub pushes: i64
+	}
+
+	pub fn default_state() -> State {
+		State { pushes: 0 }
+	}
+
+	impl State {
+		#[on]
+		fn push(&mut self, data: &[push::Data], #[write] bodies: rigid_body, teams: team) {
+			for p in data.iter() {
+				self.pushes += 1;
+				for body in bodies.iter_mut() {
+					body.1.speed += p.force / body.1.mass;
+				}
+			}
+		}
+
+		#[on_sync]
+		fn inspect(&mut self, total: inspect::Argument) {
+			*total += self.pushes;
+		}
+	}
+}
+
+#[test]
+fn derived_component_is_stored_in_snake_case_module() {
+	let _events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+	rigid_body::register();
+	team::register();
+	{
+		let list = rigid_body::LIST.write().expect("COMPONENT_LIST corrupted").into_mapped();
+		rigid_body::add_with_lock(list, 1, 2, 0);
+	}
+	team::add(1, Team::Blue);
+
+	let instance = physics::register();
+	push::trigger(10);
+	events::run_events();
+
+	assert_eq!(instance.state.read().expect("System state corrupted").pushes, 1);
+	assert_eq!(rigid_body::LIST.read().expect("COMPONENT_LIST corrupted")[0].1.speed, 5);
+
+	let mut total = 0;
+	inspect::trigger(&mut total);
+	assert_eq!(total, 1);
+	physics::unregister(&instance);
+}
+
+#[test]
+fn event_attribute_keeps_struct_as_data() {
+	let _events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+	aim::trigger(1, 2);
+	aim::trigger_data(Aim { x: 3, y: 4 });
+
+	let data = aim::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+	assert_eq!(data.len(), 1);
+	assert_eq!(data[0].x, 3);
+	assert_eq!(aim::EVENT_NAME, "derive::aim");
+}
+
+#[test]
+fn mistakes_are_reported_at_their_tokens() {
+	// only errors the macros report themselves, the wording of rustc's own errors changes
+	let cases = trybuild::TestCases::new();
+	cases.compile_fail("tests/derive_errors/*.rs");
+}
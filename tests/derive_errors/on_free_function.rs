@@ -0,0 +1,20 @@
+extern crate entity_rust;
+extern crate entity_rust_derive;
+
+use entity_rust_derive::{ event, system };
+
+#[event]
+pub struct Push {
+	pub force: i64
+}
+
+#[system]
+pub mod physics {
+	use super::push;
+
+	#[on]
+	fn push(data: &[push::Data]) {
+	}
+}
+
+fn main() {}
@@ -0,0 +1,27 @@
+extern crate entity_rust;
+extern crate entity_rust_derive;
+
+use entity_rust_derive::{ event, system, Component };
+
+#[derive(Component, Default)]
+pub struct RigidBody {
+	pub mass: i64
+}
+
+#[event]
+pub struct Push {
+	pub force: i64
+}
+
+#[system]
+pub mod physics {
+	use super::{ push, rigid_body };
+
+	impl State {
+		#[on]
+		fn push(&mut self, data: &[push::Data], #[write(all)] bodies: rigid_body) {
+		}
+	}
+}
+
+fn main() {}
@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::entities::{ self, EntityBuilder };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+component! { position, x: i64, y: i64 }
+component! { health, hp: i64 }
+tag! { player }
+
+static COMPLETE_WHEN_ADDED: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn builder_adds_all_components_before_hooks() {
+	position::register();
+	health::register();
+	player::register();
+
+	// The hook of the first component already sees the others.
+	position::on_added(|entity| {
+		let has_health = health::LIST.read().expect("COMPONENT_LIST corrupted").iter().any(|&(e, _)| e == entity);
+		if has_health && player::contains(entity) {
+			COMPLETE_WHEN_ADDED.fetch_add(1, Ordering::SeqCst);
+		}
+	});
+
+	let unit = EntityBuilder::new()
+		.with(position::Component { x: 1, y: 2 })
+		.with(health::Component { hp: 5 })
+		.with(health::Component { hp: 10 })
+		.tag::<player::Component>()
+		.spawn();
+
+	assert_eq!(COMPLETE_WHEN_ADDED.load(Ordering::SeqCst), 1);
+	let healths = health::LIST.read().expect("COMPONENT_LIST corrupted");
+	let hps : Vec<i64> = healths.iter().filter(|&&(e, _)| e == unit).map(|(_, h)| h.hp).collect();
+	assert_eq!(hps, vec![10]);
+}
+
+#[test]
+fn allocated_ids_are_unique() {
+	let first = entities::allocate();
+	let second = EntityBuilder::new().entity();
+	assert!(first != second);
+}
@@ -1,14 +1,95 @@
 #[macro_use]
 extern crate entity_rust;
-#[macro_use]
-extern crate lazy_static;
-extern crate shared_mutex;
-extern crate uuid;
 
 event!(test_event, x: i64, y: i64 );
 sync_event! { test_sync_event, x: &'a i64 }
 sync_event! { test_sync_event_2, x: i64 }
+event!{ mouse_move, x: i64, y: i64 ; coalesce = Latest }
+event!{ first_click, x: i64 ; coalesce = First }
+event!{ damage, amount: i64 ; coalesce = Merge(super::add_damage) }
+event!{ follow_up, step: i64 }
+event!{
+	#[derive(Debug, Clone, PartialEq)]
+	aim,
+	x: f32,
+	#[doc="Upwards is positive."]
+	y: f32
+}
+
+use entity_rust::events;
+
+pub fn add_damage(total: &mut damage::Data, next: damage::Data) {
+	total.amount += next.amount;
+}
+
+#[test]
+fn events_are_named_by_module_path() {
+	assert_eq!(mouse_move::EVENT_NAME, "events::mouse_move");
+	mouse_move::register();
 
+	let event = events::lookup("events::mouse_move").expect("Event was not registered");
+	assert_eq!(event.name, mouse_move::EVENT_NAME);
+	assert!(events::registered_event_names().iter().any(|name| name == "events::mouse_move"));
+	assert!(events::lookup("events::unknown").is_none());
+}
+
+#[test]
+fn coalesce_latest_keeps_last_trigger() {
+	mouse_move::trigger(1, 1);
+	mouse_move::trigger(2, 3);
+	let data = mouse_move::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+	assert_eq!(data.len(), 1);
+	assert_eq!(data[0].x, 2);
+	assert_eq!(data[0].y, 3);
+}
+
+#[test]
+fn coalesce_first_keeps_first_trigger() {
+	first_click::trigger(1);
+	first_click::trigger(2);
+	let data = first_click::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+	assert_eq!(data.len(), 1);
+	assert_eq!(data[0].x, 1);
+}
+
+#[test]
+fn trigger_after_waits_for_ticks() {
+	follow_up::trigger_next_tick(1);
+	follow_up::trigger_after(2, 2);
+	assert_eq!(follow_up::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted").len(), 0);
+
+	events::next_tick();
+	{
+		let data = follow_up::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+		assert_eq!(data.len(), 1);
+		assert_eq!(data[0].step, 1);
+	}
+
+	events::next_tick();
+	{
+		let data = follow_up::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+		assert_eq!(data.len(), 2);
+		assert_eq!(data[1].step, 2);
+	}
+	assert_eq!(follow_up::NEXT_TICK_DATA.read().expect("NEXT_TICK_DATA mutex corrupted").len(), 0);
+}
+
+#[test]
+fn coalesce_merge_combines_triggers() {
+	damage::trigger(3);
+	damage::trigger(4);
+	let data = damage::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+	assert_eq!(data.len(), 1);
+	assert_eq!(data[0].amount, 7);
+}
+
+#[test]
+fn event_data_takes_attributes() {
+	aim::trigger(0.5, -1.5);
+	let data = aim::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+	assert_eq!(data[0].clone(), aim::Data { x: 0.5, y: -1.5 });
+	assert_eq!(format!("{:?}", data[0]), "Data { x: 0.5, y: -1.5 }");
+}
 
 fn sync_event_handler(x: &i64) {
 	assert!(*x == 1);
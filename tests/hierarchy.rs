@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::entities::{ self, EntityBuilder };
+use entity_rust::hierarchy::{ self, HierarchyError };
+
+component! { armour, weight: i64 }
+
+#[test]
+fn hierarchy_is_maintained() {
+	let city = entities::allocate();
+	let barracks = entities::allocate();
+	let wall = entities::allocate();
+	let gate = entities::allocate();
+
+	hierarchy::set_parent(barracks, city).expect("Could not attach barracks");
+	hierarchy::set_parent(wall, city).expect("Could not attach wall");
+	hierarchy::set_parent(gate, wall).expect("Could not attach gate");
+
+	assert_eq!(hierarchy::children_of(city), vec![barracks, wall]);
+	assert_eq!(hierarchy::ancestors(gate), vec![wall, city]);
+	assert_eq!(hierarchy::descendants(city), vec![barracks, wall, gate]);
+	assert_eq!(hierarchy::set_parent(city, gate), Err(HierarchyError::Cycle { child: city, parent: gate }));
+
+	hierarchy::set_parent(gate, barracks).expect("Could not move gate");
+	assert_eq!(hierarchy::parent_of(gate), Some(barracks));
+	assert_eq!(hierarchy::children_of(wall), vec![]);
+
+	entities::despawn(barracks);
+	assert_eq!(hierarchy::children_of(city), vec![wall]);
+	assert_eq!(hierarchy::parent_of(gate), None);
+}
+
+#[test]
+fn despawn_recursive_removes_descendants() {
+	armour::register();
+	let unit = EntityBuilder::new().with(armour::Component { weight: 3 }).spawn();
+	let weapon = EntityBuilder::new().with(armour::Component { weight: 1 }).spawn();
+	let scope = entities::allocate();
+	hierarchy::set_parent(weapon, unit).expect("Could not attach weapon");
+	hierarchy::set_parent(scope, weapon).expect("Could not attach scope");
+
+	hierarchy::despawn_recursive(unit);
+
+	let armours = armour::LIST.read().expect("COMPONENT_LIST corrupted");
+	assert!(!armours.iter().any(|&(e, _)| e == unit || e == weapon));
+	assert_eq!(hierarchy::parent_of(scope), None);
+	assert_eq!(hierarchy::children_of(unit), vec![]);
+}
@@ -1,11 +1,5 @@
-#![feature(type_macros)]
-
 #[macro_use]
 extern crate entity_rust;
-#[macro_use]
-extern crate lazy_static;
-extern crate shared_mutex;
-extern crate uuid;
 
 // use std::any::Any;
 
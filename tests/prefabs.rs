@@ -0,0 +1,88 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::prefabs::{ self, Prefab, PrefabError };
+use entity_rust::entities::EntityID;
+
+component! { position, x: i64, y: i64 }
+component! { health, hp: i64, max_hp: i64 }
+component! { banner, text: String }
+tag! { player }
+
+fn setup() {
+	position::register();
+	health::register();
+	banner::register();
+	player::register();
+	prefab_component!{ position, x, y }
+	prefab_component!{ health, hp, max_hp }
+	prefab_component!{ banner, text }
+	prefabs::register_tag::<player::Component>("player");
+}
+
+fn health_of(entity: EntityID) -> i64 {
+	let list = health::LIST.read().expect("COMPONENT_LIST corrupted");
+	list.iter().find(|&&(e, _)| e == entity).expect("Entity has no health").1.hp
+}
+
+#[test]
+fn prefabs_spawn_with_overrides() {
+	setup();
+	prefabs::register(Prefab::new("soldier")
+		.with_default::<position::Component>()
+		.with(|| health::Component { hp: 10, max_hp: 10 })
+		.tag::<player::Component>());
+
+	let private = prefabs::spawn("soldier").expect("No soldier prefab");
+	let captain = prefabs::instantiate("soldier").expect("No soldier prefab")
+		.set(|h: &mut health::Component| h.hp = 20).expect("Soldiers have health")
+		.spawn();
+
+	assert_eq!(health_of(private), 10);
+	assert_eq!(health_of(captain), 20);
+	assert!(player::contains(captain));
+	assert!(prefabs::spawn("dragon").is_none());
+	assert!(prefabs::instantiate("soldier").expect("No soldier prefab")
+		.set(|b: &mut banner::Component| b.text = "Ensign".to_string()).is_none());
+}
+
+#[test]
+fn prefabs_load_from_text() {
+	setup();
+	let names = prefabs::load_str("
+		# Units
+		[archer]
+		position x=3
+		health hp=7
+		banner text=\"Long bow\"
+		player
+	").expect("Could not load prefabs");
+	assert_eq!(names, vec!["archer".to_string()]);
+
+	let archer = prefabs::spawn("archer").expect("No archer prefab");
+	assert_eq!(health_of(archer), 7);
+	let banners = banner::LIST.read().expect("COMPONENT_LIST corrupted");
+	assert_eq!(banners.iter().find(|&&(e, _)| e == archer).expect("Archer has no banner").1.text, "Long bow");
+}
+
+#[test]
+fn prefab_files_report_errors() {
+	setup();
+	match prefabs::load_str("[knight]\nhealth hp=lots") {
+		Err(PrefabError::Field { line: 2, .. }) => {},
+		other => panic!("Expected a field error, got {:?}", other)
+	}
+	match prefabs::load_str("[knight]\nhorse") {
+		Err(PrefabError::UnknownComponent { line: 2, ref label }) if label == "horse" => {},
+		other => panic!("Expected an unknown component error, got {:?}", other)
+	}
+	match prefabs::load_str("[ ]\nhealth hp=3") {
+		Err(PrefabError::Syntax { line: 1, .. }) => {},
+		other => panic!("Expected a syntax error for the empty name, got {:?}", other)
+	}
+	match prefabs::load_str("[knight]\nhealth hp=3\n[knight]\nposition") {
+		Err(PrefabError::Syntax { line: 3, .. }) => {},
+		other => panic!("Expected a syntax error for the second knight, got {:?}", other)
+	}
+	assert!(prefabs::get("knight").is_none());
+}
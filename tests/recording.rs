@@ -0,0 +1,187 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::{ events, timers };
+use entity_rust::recording::{ Recorder, Recording, Replay };
+use std::{ env, fs, panic, process };
+use std::sync::Mutex;
+use std::time::Duration;
+
+event!{ key_press, key: u32 }
+event!{ scored, points: i64 }
+event!{ alarm, bell: u32 }
+event!{ tap, finger: u32 }
+event!{ fault, code: u32 }
+
+codec!{ key_press::Data { key } }
+codec!{ scored::Data { points } }
+codec!{ alarm::Data { bell } }
+codec!{ tap::Data { finger } }
+
+// recorders observe every trigger, so the tests take turns
+static RUNS: Mutex<()> = Mutex::new(());
+
+system!( score_system {
+	use super::{ key_press, scored };
+
+	state { total: i64 } { total = 0 }
+
+	on key_press, {}, {}, (self, data) => {
+		for press in data.iter() {
+			scored::trigger(press.key as i64 * 10);
+		}
+	}
+
+	on scored, {}, {}, (self, data) => {
+		for score in data.iter() {
+			self.total += score.points;
+		}
+	}
+});
+
+system!( alarm_system {
+	use super::alarm;
+
+	state { rings: usize } { rings = 0 }
+
+	on alarm, {}, {}, (self, data) => {
+		self.rings += data.len();
+	}
+});
+
+system!( clock_system {
+	use entity_rust::tick;
+	use std::time::Duration;
+
+	state { elapsed: Duration } { elapsed = Duration::from_secs(0) }
+
+	on tick, {}, {}, (self, data) => {
+		for tick in data.iter() {
+			self.elapsed += tick.step;
+		}
+	}
+});
+
+system!( faulty_system {
+	use super::fault;
+
+	state { } { }
+
+	on fault, {}, {}, (self, data) => {
+		panic!("Fault {}", data[0].code);
+	}
+});
+
+fn play() {
+	key_press::trigger(1);
+	events::next_tick();
+	events::run_tick().expect("Tick failed");
+
+	key_press::trigger(2);
+	key_press::trigger_after(2, 3);
+	events::next_tick();
+	events::run_tick().expect("Tick failed");
+
+	for _ in 0..3 {
+		events::next_tick();
+		events::run_tick().expect("Tick failed");
+	}
+}
+
+#[test]
+fn replay_reproduces_recorded_run() {
+	let _run = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+	events::register_codec::<key_press::Event>();
+	events::register_codec::<scored::Event>();
+
+	let recorded = score_system::register();
+	let recorder = Recorder::start();
+	play();
+	let recording = recorder.stop();
+	score_system::unregister(&recorded);
+
+	assert_eq!(recorded.state.read().expect("System state corrupted").total, 60);
+	// scores are triggered by a handler, so only the key presses are recorded
+	assert_eq!(recording.records.len(), 3);
+	assert!(recording.records.iter().all(|r| r.event_name == key_press::EVENT_NAME));
+
+	let path = env::temp_dir().join(format!("entity_rust_replay_test_{}.rec", process::id()));
+	recording.save(&path).expect("Could not save recording");
+	let loaded = Recording::load(&path).expect("Could not load recording");
+	fs::remove_file(&path).expect("Could not remove recording");
+	assert_eq!(loaded, recording);
+
+	let replayed = score_system::register();
+	let mut replay = Replay::new(loaded);
+	replay.run().expect("Replay failed");
+	for _ in 0..3 {
+		replay.step().expect("Replay failed");
+	}
+
+	assert_eq!(replayed.state.read().expect("System state corrupted").total, 60);
+}
+
+#[test]
+fn timers_fire_again_on_replay() {
+	let _run = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+	let step = Duration::from_millis(10);
+	events::register_codec::<alarm::Event>();
+
+	let recorded = alarm_system::register();
+	let recorder = Recorder::start();
+	let bell = timers::every(step, || alarm::trigger(1));
+	for _ in 0..3 {
+		entity_rust::tick_once(step).expect("Tick failed");
+	}
+	let recording = recorder.stop();
+	alarm_system::unregister(&recorded);
+
+	assert_eq!(recorded.state.read().expect("System state corrupted").rings, 3);
+	assert!(recording.records.iter().all(|r| r.event_name != alarm::EVENT_NAME));
+
+	let replayed = alarm_system::register();
+	let mut replay = Replay::with_timers(recording, step);
+	for _ in 0..3 {
+		replay.step().expect("Replay failed");
+	}
+	bell.cancel();
+	alarm_system::unregister(&replayed);
+
+	assert_eq!(replayed.state.read().expect("System state corrupted").rings, 3);
+}
+
+#[test]
+fn ticks_are_replayed() {
+	let _run = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+	let recorded = clock_system::register();
+	let recorder = Recorder::start();
+	for millis in 1..4 {
+		entity_rust::tick_once(Duration::from_millis(millis)).expect("Tick failed");
+	}
+	assert!(recorder.unrecorded_events().is_empty());
+	let recording = recorder.stop();
+	clock_system::unregister(&recorded);
+	assert_eq!(recorded.state.read().expect("System state corrupted").elapsed, Duration::from_millis(6));
+
+	let replayed = clock_system::register();
+	Replay::new(recording).run().expect("Replay failed");
+	clock_system::unregister(&replayed);
+	assert_eq!(replayed.state.read().expect("System state corrupted").elapsed, Duration::from_millis(6));
+}
+
+#[test]
+fn triggers_after_a_panicking_handler_are_external() {
+	let _run = RUNS.lock().unwrap_or_else(|e| e.into_inner());
+	events::register_codec::<tap::Event>();
+
+	let faulty = faulty_system::register();
+	fault::trigger(1);
+	assert!(panic::catch_unwind(events::run_tick).is_err());
+	faulty_system::unregister(&faulty);
+
+	let recorder = Recorder::start();
+	tap::trigger(2);
+	let recording = recorder.stop();
+	assert_eq!(recording.records.len(), 1);
+	assert_eq!(recording.records[0].event_name, tap::EVENT_NAME);
+}
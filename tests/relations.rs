@@ -0,0 +1,42 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::entities::{ self, EntityID };
+
+event!{ target_lost, source: super::EntityID, target: super::EntityID }
+
+relation!{ works_at }
+relation!{ targets ; on_broken = super::target_lost }
+
+#[test]
+fn relations_are_queried_from_both_ends() {
+	works_at::register();
+	let (miller, baker, mill) = (entities::allocate(), entities::allocate(), entities::allocate());
+
+	assert!(works_at::add(miller, mill));
+	assert!(!works_at::add(miller, mill));
+	works_at::add(baker, mill);
+
+	assert_eq!(works_at::targets(miller), vec![mill]);
+	assert_eq!(works_at::sources(mill), vec![miller, baker]);
+	assert!(works_at::remove_pair(baker, mill));
+	assert!(!works_at::contains(baker, mill));
+
+	entities::despawn(mill);
+	assert_eq!(works_at::targets(miller), vec![]);
+}
+
+#[test]
+fn despawning_an_end_triggers_broken_event() {
+	targets::register();
+	let (archer, deer, boar) = (entities::allocate(), entities::allocate(), entities::allocate());
+	targets::add(archer, deer);
+	targets::add(archer, boar);
+
+	entities::despawn(deer);
+	assert_eq!(targets::targets(archer), vec![boar]);
+
+	let lost = target_lost::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted");
+	assert_eq!(lost.len(), 1);
+	assert_eq!((lost[0].source, lost[0].target), (archer, deer));
+}
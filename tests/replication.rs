@@ -0,0 +1,332 @@
+#![cfg(feature = "replication")]
+
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::{ components, entities, events, replication };
+use entity_rust::codec::Codec;
+use entity_rust::entities::{ EntityBuilder, EntityID };
+use entity_rust::recording::{ Record, Recorder };
+use entity_rust::replication::{ Client, EventReceiver, EventSender, RemoteEvent, ReplicationError, Server, TickSource };
+use std::env;
+use std::io::Write;
+use std::net::{ TcpListener, TcpStream };
+use std::process::Command;
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::thread;
+use std::time::{ Duration, Instant };
+
+component! { #[derive(Debug, Clone, PartialEq, Default)] position, x: i64, y: i64 }
+component! { score, points: i64 }
+component! { cargo, manifest: String }
+tag! { selected }
+event!{ ordered, unit: u64 }
+event!{ hailed, ship: u64 }
+event!{ whispered, ship: u64 }
+event!{ boarded, ship: u64 }
+
+codec!{ position::Component { x, y } }
+codec!{ ordered::Data { unit } }
+codec!{ cargo::Component { manifest } }
+codec!{ hailed::Data { ship } }
+codec!{ boarded::Data { ship } }
+
+system!( order_system {
+	use super::ordered;
+	use entity_rust::events;
+
+	state { orders: Vec<(u64, u64)> } { orders = vec![] }
+
+	on ordered, {}, {}, (self, data) => {
+		for order in data.iter() {
+			self.orders.push((events::current_tick(), order.unit));
+		}
+	}
+});
+
+const SERVER_ADDRESS: &str = "ENTITY_RUST_REPLICATION_SERVER";
+
+// servers replicate every list that is marked when they are bound, so the tests that
+// bind one take turns
+static SERVERS: Mutex<()> = Mutex::new(());
+
+// the tests that step ticks take turns, so they can count on the tick they are in
+static TICKS: Mutex<()> = Mutex::new(());
+
+fn setup() {
+	position::register();
+	score::register();
+	selected::register();
+	components::register_codec::<position::List>();
+	components::register_codec::<selected::List>();
+	replication::replicate::<position::List>();
+	replication::replicate::<selected::List>();
+}
+
+fn positions() -> Vec<(EntityID, position::Component)> {
+	position::LIST.read().expect("COMPONENT_LIST corrupted").clone()
+}
+
+// Runs as the client in a child process started by `server_replicates_to_client`,
+// which runs ignored tests.
+#[test]
+#[ignore]
+fn replica() {
+	let address = match env::var(SERVER_ADDRESS) {
+		Ok(address) => address,
+		Err(_) => return
+	};
+	setup();
+
+	let mut client = Client::connect(&address[..]).expect("Could not connect");
+	client.set_timeout(Some(Duration::from_secs(10))).expect("Could not set timeout");
+
+	client.receive().expect("Did not receive the world");
+	assert_eq!(positions(), vec![(0, position::Component { x: 1, y: 2 }), (1, position::Component { x: 3, y: 4 })]);
+	assert!(selected::contains(1));
+	assert!(score::LIST.read().expect("COMPONENT_LIST corrupted").is_empty());
+	assert_eq!(entities::next_entity_id(), 2);
+
+	client.receive().expect("Did not receive the changes");
+	assert_eq!(positions(), vec![(0, position::Component { x: 5, y: 2 })]);
+	assert!(!selected::contains(1));
+}
+
+#[test]
+fn server_replicates_to_client() {
+	if env::var(SERVER_ADDRESS).is_ok() {
+		return;
+	}
+	let _servers = SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+	setup();
+
+	let mut server = Server::bind("127.0.0.1:0").expect("Could not bind");
+	let address = server.local_addr().expect("No local address").to_string();
+	let mut replica = Command::new(env::current_exe().expect("No test executable"))
+		.args(["replica", "--exact", "--ignored", "--nocapture"])
+		.env(SERVER_ADDRESS, &address)
+		.spawn()
+		.expect("Could not start the replica");
+
+	let started = Instant::now();
+	while server.clients() == 0 {
+		assert!(started.elapsed() < Duration::from_secs(10), "The replica did not connect");
+		server.accept().expect("Could not accept");
+		thread::sleep(Duration::from_millis(10));
+	}
+
+	let moved = EntityBuilder::new()
+		.with(position::Component { x: 1, y: 2 })
+		.with(score::Component { points: 10 })
+		.spawn();
+	let removed = EntityBuilder::new()
+		.with(position::Component { x: 3, y: 4 })
+		.tag::<selected::Component>()
+		.spawn();
+	server.update().expect("Could not update");
+
+	for &mut (e, ref mut p) in position::write().iter_mut() {
+		if e == moved {
+			p.x = 5;
+		}
+	}
+	entities::despawn(removed);
+	server.update().expect("Could not update");
+
+	assert!(replica.wait().expect("The replica did not run").success());
+}
+
+#[test]
+fn stalled_clients_are_disconnected() {
+	if env::var(SERVER_ADDRESS).is_ok() {
+		return;
+	}
+	let _servers = SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+	cargo::register();
+	components::register_codec::<cargo::List>();
+	replication::replicate::<cargo::List>();
+
+	let mut server = Server::bind("127.0.0.1:0").expect("Could not bind");
+	server.set_write_timeout(Duration::from_millis(50)).expect("Could not set the timeout");
+	// the client never reads, so the changes pile up until the server can not send them
+	let _stalled = TcpStream::connect(server.local_addr().expect("No local address")).expect("Could not connect");
+	let ship = EntityBuilder::new().with(cargo::Component { manifest: String::new() }).spawn();
+
+	let started = Instant::now();
+	server.accept().expect("Could not accept");
+	assert_eq!(server.clients(), 1);
+	let mut round = 0;
+	while server.clients() > 0 {
+		assert!(started.elapsed() < Duration::from_secs(30), "The stalled client was not disconnected");
+		round += 1;
+		cargo::write()[0].1.manifest = round.to_string().repeat(1 << 20);
+		server.update().expect("Could not update");
+	}
+
+	cargo::remove(ship);
+}
+
+#[test]
+fn long_frames_are_rejected() {
+	let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+	let mut client = Client::connect(listener.local_addr().expect("No local address")).expect("Could not connect");
+	let (mut stream, _) = listener.accept().expect("Could not accept");
+
+	let mut header = vec![];
+	usize::MAX.encode(&mut header);
+	stream.write_all(&header).expect("Could not write");
+
+	let started = Instant::now();
+	loop {
+		assert!(started.elapsed() < Duration::from_secs(10), "The frame did not arrive");
+		match client.poll() {
+			Ok(0) => thread::sleep(Duration::from_millis(10)),
+			Err(ReplicationError::FrameTooLong(len)) => {
+				assert_eq!(len, usize::MAX);
+				break;
+			},
+			other => panic!("Unexpected result {:?}", other)
+		}
+	}
+}
+
+#[test]
+fn forwarded_events_are_delivered_after_the_delay() {
+	if env::var(SERVER_ADDRESS).is_ok() {
+		return;
+	}
+	let _ticks = TICKS.lock().unwrap_or_else(|e| e.into_inner());
+	events::register_codec::<ordered::Event>();
+	replication::forward_event::<ordered::Event>();
+	let system = order_system::register();
+
+	// sender and receiver share the tick counter of this process
+	let mut receiver = EventReceiver::bind("127.0.0.1:0").expect("Could not bind");
+	let address = receiver.local_addr().expect("No local address");
+	let tick_source : TickSource = Arc::new(events::current_tick);
+	let mut sender = EventSender::connect(address, 2, tick_source).expect("Could not connect");
+
+	let start = events::current_tick();
+	ordered::trigger(7);
+	events::run_tick().expect("Tick failed");
+	assert_eq!(sender.flush().expect("Could not flush"), 1);
+
+	let started = Instant::now();
+	while receiver.pending() == 0 {
+		assert!(started.elapsed() < Duration::from_secs(10), "The trigger did not arrive");
+		assert_eq!(receiver.poll().expect("Could not poll"), 0);
+		thread::sleep(Duration::from_millis(10));
+	}
+
+	events::next_tick();
+	assert_eq!(receiver.poll().expect("Could not poll"), 0);
+	events::run_tick().expect("Tick failed");
+	events::next_tick();
+	assert_eq!(receiver.poll().expect("Could not poll"), 1);
+	events::run_tick().expect("Tick failed");
+	sender.stop();
+
+	// the trigger runs locally right away and again when it is delivered
+	assert_eq!(system.state.read().expect("System state corrupted").orders, vec![(start, 7), (start + 2, 7)]);
+	assert_eq!(receiver.pending(), 0);
+}
+
+#[test]
+fn senders_count_the_ticks_of_the_receiver() {
+	let _ticks = TICKS.lock().unwrap_or_else(|e| e.into_inner());
+	events::register_codec::<boarded::Event>();
+	replication::forward_event::<boarded::Event>();
+
+	// the receiving world is far ahead of the sending one, as a server is of a new client
+	let local = events::current_tick();
+	let remote = local + 1000;
+	let receiver_tick = Arc::new(AtomicU64::new(remote));
+	let source_tick = receiver_tick.clone();
+	let mut receiver = EventReceiver::bind("127.0.0.1:0").expect("Could not bind");
+	let address = receiver.local_addr().expect("No local address");
+	let mut sender = EventSender::connect(address, 2, Arc::new(move || source_tick.load(Ordering::SeqCst))).expect("Could not connect");
+
+	boarded::trigger(4);
+	// a sender that lost track of the receiver asks for a tick that has passed
+	receiver_tick.store(local, Ordering::SeqCst);
+	boarded::trigger(5);
+	assert_eq!(sender.flush().expect("Could not flush"), 2);
+	sender.stop();
+	events::run_tick().expect("Tick failed");
+
+	events::set_current_tick(remote);
+	let recorder = Recorder::start();
+	let started = Instant::now();
+	let mut delivered = 0;
+	while delivered == 0 {
+		assert!(started.elapsed() < Duration::from_secs(10), "The triggers did not arrive");
+		delivered += receiver.poll().expect("Could not poll");
+		thread::sleep(Duration::from_millis(10));
+	}
+	assert_eq!(receiver.late(), 1);
+	events::run_tick().expect("Tick failed");
+
+	events::next_tick();
+	assert_eq!(receiver.poll().expect("Could not poll"), 0);
+	events::next_tick();
+	assert_eq!(receiver.poll().expect("Could not poll"), 1);
+	events::run_tick().expect("Tick failed");
+	assert_eq!(receiver.late(), 1);
+
+	// delivered triggers are recorded as input of the tick before they run
+	let boardings : Vec<Record> = recorder.stop().records.into_iter().filter(|r| r.event_name == boarded::EVENT_NAME).collect();
+	assert_eq!(boardings.iter().map(|r| r.tick).collect::<Vec<u64>>(), vec![remote - 1, remote + 1]);
+}
+
+fn frame(bytes: &[u8]) -> Vec<u8> {
+	let mut frame = vec![];
+	bytes.len().encode(&mut frame);
+	frame.extend_from_slice(bytes);
+	frame
+}
+
+#[test]
+fn bad_triggers_do_not_stop_the_others() {
+	events::register_codec::<hailed::Event>();
+	replication::forward_event::<hailed::Event>();
+
+	let mut receiver = EventReceiver::bind("127.0.0.1:0").expect("Could not bind");
+	let mut stream = TcpStream::connect(receiver.local_addr().expect("No local address")).expect("Could not connect");
+
+	let mut data = vec![];
+	hailed::Data { ship: 3 }.encode(&mut data);
+	let hail = RemoteEvent { tick: 0, event_name: hailed::EVENT_NAME.to_string(), data: data.clone() };
+	let whisper = RemoteEvent { tick: 0, event_name: whispered::EVENT_NAME.to_string(), data: data };
+	let mut bytes = frame(&[1, 2, 3]);
+	bytes.extend(frame(&entity_rust::codec::to_bytes(&whisper)));
+	bytes.extend(frame(&entity_rust::codec::to_bytes(&hail)));
+	stream.write_all(&bytes).expect("Could not write");
+
+	let started = Instant::now();
+	let mut delivered = 0;
+	while delivered == 0 {
+		assert!(started.elapsed() < Duration::from_secs(10), "The triggers did not arrive");
+		delivered += receiver.poll().expect("Could not poll");
+		thread::sleep(Duration::from_millis(10));
+	}
+	assert_eq!(delivered, 1);
+
+	let rejected = receiver.take_rejected();
+	assert_eq!(rejected.len(), 2);
+	match rejected[0] {
+		ReplicationError::Decode(_) => (),
+		ref other => panic!("Expected a decode error, got {:?}", other)
+	}
+	match rejected[1] {
+		ReplicationError::UnknownEvent(ref name) if name == whispered::EVENT_NAME => (),
+		ref other => panic!("Expected an unknown event, got {:?}", other)
+	}
+	assert_eq!(receiver.senders(), 1);
+}
+
+#[test]
+#[should_panic(expected = "has no codec registered")]
+fn remote_events_need_a_codec() {
+	replication::forward_event::<whispered::Event>();
+}
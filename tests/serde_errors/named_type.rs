@@ -0,0 +1,8 @@
+#[macro_use]
+extern crate entity_rust;
+
+pub struct Health(pub u32);
+
+component! { #[serde] health: super::Health }
+
+fn main() {}
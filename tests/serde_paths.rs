@@ -0,0 +1,42 @@
+#![cfg(feature = "serde")]
+#[macro_use]
+extern crate entity_rust as ecs;
+extern crate trybuild;
+
+use ecs::serialization;
+
+// the serde derives find this crate under its new name, and #[serde] can be put
+// after other attributes
+component! { #[derive(Debug, PartialEq)] #[serde] cargo, crates: u32 }
+event! { #[doc="Sent when a ship docks."] #[serde] docked, port: String }
+
+system!( harbour_system {
+	use super::docked;
+
+	state #[doc="Ships in port."] #[serde] { ships: u32 } { ships = 0 }
+
+	on docked, {}, {}, (self, data) => {
+		self.ships += data.len() as u32;
+	}
+});
+
+#[test]
+fn serde_attributes_follow_other_attributes() {
+	let cargo = cargo::Component { crates: 4 };
+	let bytes = serialization::to_bytes(&cargo).expect("Could not serialize");
+	assert_eq!(serialization::from_bytes::<cargo::Component>(&bytes).expect("Could not deserialize"), cargo);
+
+	let data = docked::Data { port: "Lisbon".to_string() };
+	let bytes = serialization::to_bytes(&data).expect("Could not serialize");
+	assert_eq!(serialization::from_bytes::<docked::Data>(&bytes).expect("Could not deserialize").port, "Lisbon");
+
+	let state = harbour_system::State { ships: 2 };
+	let bytes = serialization::to_bytes(&state).expect("Could not serialize");
+	assert_eq!(serialization::from_bytes::<harbour_system::State>(&bytes).expect("Could not deserialize").ships, 2);
+}
+
+#[test]
+fn serde_on_named_types_is_rejected() {
+	let cases = trybuild::TestCases::new();
+	cases.compile_fail("tests/serde_errors/*.rs");
+}
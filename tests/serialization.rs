@@ -0,0 +1,81 @@
+#![cfg(feature = "serde")]
+
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::{ components, events, serialization, snapshot };
+use entity_rust::entities::EntityBuilder;
+use entity_rust::snapshot::Snapshot;
+use std::collections::BTreeMap;
+
+component! { #[serde] #[derive(Debug, Clone, PartialEq)] cargo, goods: ::std::collections::BTreeMap<String, u32>, destination: Option<String> }
+component! { #[doc="Weight carried below deck."] #[serde] ballast, tons: u32 }
+event! { #[serde] ordered, goods: String, amount: u32 }
+
+#[derive(Debug, Clone, PartialEq, entity_rust::serde::Serialize, entity_rust::serde::Deserialize)]
+#[serde(crate = "entity_rust::serde")]
+enum Order {
+	Idle,
+	Deliver(String, u32),
+	Wait { ticks: u64 }
+}
+
+system!( trade_system {
+	use super::ordered;
+
+	state #[serde] { orders: Vec<super::Order> } { orders = vec![super::Order::Idle] }
+
+	on ordered, {}, {}, (self, data) => {
+		for order in data.iter() {
+			self.orders.push(super::Order::Deliver(order.goods.clone(), order.amount));
+		}
+	}
+});
+
+#[test]
+fn serde_values_round_trip() {
+	let orders = vec![Order::Idle, Order::Deliver("wool".to_string(), 3), Order::Wait { ticks: 2 }];
+	let bytes = serialization::to_bytes(&orders).expect("Could not serialize");
+	assert_eq!(serialization::from_bytes::<Vec<Order>>(&bytes).expect("Could not deserialize"), orders);
+
+	// the format matches the codec for the types both support
+	let pair = (7u64, Some("grain".to_string()));
+	assert_eq!(serialization::to_bytes(&pair).expect("Could not serialize"), entity_rust::codec::to_bytes(&pair));
+}
+
+#[test]
+fn serde_components_keep_default() {
+	let ballast : ballast::Component = Default::default();
+	let bytes = serialization::to_bytes(&ballast).expect("Could not serialize");
+	assert_eq!(serialization::from_bytes::<ballast::Component>(&bytes).expect("Could not deserialize").tons, 0);
+}
+
+#[test]
+fn serde_types_are_saved_in_snapshots() {
+	cargo::register();
+	components::register_serde::<cargo::List>();
+	events::register_serde::<ordered::Event>();
+	let system = trade_system::register();
+	snapshot::add_serde_state("trade", system.state.clone());
+
+	let mut goods = BTreeMap::new();
+	goods.insert("wool".to_string(), 4);
+	let ship = EntityBuilder::new().with(cargo::Component { goods: goods, destination: Some("port".to_string()) }).spawn();
+	ordered::trigger_after(1, "wool".to_string(), 2);
+
+	let saved = snapshot::take();
+	let saved_cargo = cargo::LIST.read().expect("COMPONENT_LIST corrupted").clone();
+	cargo::remove(ship);
+	system.state.write().expect("System state corrupted").orders.clear();
+	ordered::NEXT_TICK_DATA.write().expect("NEXT_TICK_DATA mutex corrupted").clear();
+
+	let mut bytes = vec![];
+	saved.write_to(&mut bytes).expect("Could not write snapshot");
+	Snapshot::read_from(&mut &bytes[..]).expect("Could not read snapshot").restore().expect("Could not restore snapshot");
+
+	assert_eq!(*cargo::LIST.read().expect("COMPONENT_LIST corrupted"), saved_cargo);
+	events::next_tick();
+	events::run_tick().expect("Tick failed");
+	let orders = system.state.read().expect("System state corrupted").orders.clone();
+	assert_eq!(orders, vec![Order::Idle, Order::Deliver("wool".to_string(), 2)]);
+}
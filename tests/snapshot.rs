@@ -0,0 +1,201 @@
+#[macro_use]
+extern crate entity_rust;
+
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration;
+use entity_rust::{ components, entities, events, snapshot, timers };
+use entity_rust::entities::{ EntityBuilder, EntityID };
+use entity_rust::snapshot::{ Snapshot, SnapshotError };
+
+component! { #[derive(Debug, Clone, PartialEq)] position, x: i64, y: i64 }
+component! { sprite, name: String }
+tag! { player }
+relation! { follows }
+component! { #[derive(Debug, Clone, PartialEq)] ammo, rounds: u32 }
+event! { damaged, amount: i64 }
+event! { reloaded, rounds: u32 }
+event! { healed, amount: i64 }
+
+codec!{ position::Component { x, y } }
+codec!{ damaged::Data { amount } }
+codec!{ ammo::Component { rounds } }
+codec!{ reloaded::Data { rounds } }
+codec!{ healed::Data { amount } }
+
+// restoring replaces the world, so the tests take turns
+static WORLD: Mutex<()> = Mutex::new(());
+
+system!( damage_system {
+	use super::damaged;
+
+	state { total: i64 } { total = 0 }
+
+	on damaged, {}, {}, (self, data) => {
+		for damage in data.iter() {
+			self.total += damage.amount;
+		}
+	}
+});
+
+codec!{ damage_system::State { total } }
+
+system!( heal_system {
+	use super::healed;
+
+	state { runs: usize } { runs = 0 }
+
+	on healed, {}, {}, (self, data) => {
+		self.runs += 1;
+	}
+});
+
+fn positions() -> Vec<(EntityID, position::Component)> {
+	position::LIST.read().expect("COMPONENT_LIST corrupted").clone()
+}
+
+#[test]
+fn restore_replaces_saved_world() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	position::register();
+	sprite::register();
+	player::register();
+	follows::register();
+	components::register_codec::<position::List>();
+	components::register_codec::<player::List>();
+	components::register_codec::<follows::List>();
+	events::register_codec::<damaged::Event>();
+
+	let system = damage_system::register();
+	snapshot::add_state("damage", system.state.clone());
+	assert_eq!(snapshot::unsaved_components(), vec![sprite::LABEL]);
+
+	let leader = EntityBuilder::new()
+		.with(position::Component { x: 1, y: 2 })
+		.tag::<player::Component>()
+		.spawn();
+	let follower = EntityBuilder::new().with(position::Component { x: 3, y: 4 }).spawn();
+	follows::add(follower, leader);
+	system.state.write().expect("System state corrupted").total = 5;
+	damaged::trigger_after(2, 7);
+
+	let saved_positions = positions();
+	let next_entity = entities::next_entity_id();
+	let mut bytes = vec![];
+	snapshot::take().write_to(&mut bytes).expect("Could not write snapshot");
+
+	entities::despawn(leader);
+	entities::allocate();
+	system.state.write().expect("System state corrupted").total = 0;
+	*damaged::NEXT_TICK_DATA.write().expect("NEXT_TICK_DATA mutex corrupted") = vec![];
+
+	let loaded = Snapshot::read_from(&mut &bytes[..]).expect("Could not read snapshot");
+	loaded.restore().expect("Could not restore snapshot");
+
+	assert_eq!(positions(), saved_positions);
+	assert!(player::contains(leader));
+	assert_eq!(follows::targets(follower), vec![leader]);
+	assert_eq!(follows::sources(leader), vec![follower]);
+	assert_eq!(entities::next_entity_id(), next_entity);
+
+	// the queued damage is due in two ticks
+	for _ in 0..2 {
+		events::next_tick();
+		events::run_tick().expect("Tick failed");
+	}
+	assert_eq!(system.state.read().expect("System state corrupted").total, 12);
+}
+
+#[test]
+fn restore_rejects_unknown_parts() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	let snapshot = Snapshot { states: vec![("missing".to_string(), vec![])], ..Snapshot::default() };
+	match snapshot.restore() {
+		Err(SnapshotError::UnknownState(ref name)) if name == "missing" => (),
+		other => panic!("Unexpected result {:?}", other)
+	}
+
+	assert!(Snapshot::read_from(&mut &b"not a snapshot"[..]).is_err());
+}
+
+#[test]
+fn failed_restore_changes_nothing() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	ammo::register();
+	components::register_codec::<ammo::List>();
+	events::register_codec::<reloaded::Event>();
+
+	let mut snapshot = snapshot::take();
+	let gunner = EntityBuilder::new().with(ammo::Component { rounds: 6 }).spawn();
+	let tick = events::current_tick();
+	let next_entity = entities::next_entity_id();
+
+	// the saved list is fine, but the queues of the event are cut short
+	let queues = snapshot.events.iter_mut().find(|&&mut (ref name, _)| name == reloaded::EVENT_NAME).expect("Event queues were not saved");
+	queues.1.clear();
+	snapshot.tick = tick + 10;
+	match snapshot.restore() {
+		Err(SnapshotError::Decode(ref name, _)) if name == reloaded::EVENT_NAME => (),
+		other => panic!("Unexpected result {:?}", other)
+	}
+
+	assert_eq!(ammo::LIST.read().expect("COMPONENT_LIST corrupted").clone(), vec![(gunner, ammo::Component { rounds: 6 })]);
+	assert_eq!(events::current_tick(), tick);
+	assert_eq!(entities::next_entity_id(), next_entity);
+}
+
+#[test]
+fn restore_drops_events_without_saved_data() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	events::register_codec::<healed::Event>();
+	let system = heal_system::register();
+
+	let snapshot = snapshot::take();
+	healed::trigger(3);
+	healed::trigger_after(1, 4);
+	snapshot.restore().expect("Could not restore snapshot");
+
+	events::run_tick().expect("Tick failed");
+	events::next_tick();
+	events::run_tick().expect("Tick failed");
+	assert_eq!(system.state.read().expect("System state corrupted").runs, 0);
+}
+
+#[test]
+fn restore_rewinds_timers() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	static FIRED: AtomicUsize = AtomicUsize::new(0);
+	timers::advance(Duration::from_secs(1));
+	let fuse = timers::after(Duration::from_secs(2), || { FIRED.fetch_add(1, Ordering::SeqCst); }).named("fuse");
+	let clock = timers::now();
+	let saved = snapshot::take();
+
+	timers::advance(Duration::from_millis(1500));
+	saved.restore().expect("Could not restore snapshot");
+	assert_eq!(timers::now(), clock);
+
+	// the fuse is due two seconds after the saved clock again
+	timers::advance(Duration::from_millis(1500));
+	assert_eq!(FIRED.load(Ordering::SeqCst), 0);
+	timers::advance(Duration::from_millis(500));
+	assert_eq!(FIRED.load(Ordering::SeqCst), 1);
+	assert!(!fuse.is_active());
+}
+
+#[test]
+fn restore_requires_the_saved_timers() {
+	let _world = WORLD.lock().unwrap_or_else(|e| e.into_inner());
+	let bell = timers::after(Duration::from_secs(5), || ()).named("bell");
+	let saved = snapshot::take();
+	let tick = events::current_tick();
+
+	bell.cancel();
+	timers::advance(Duration::from_secs(1));
+	let clock = timers::now();
+	match saved.restore() {
+		Err(SnapshotError::UnknownTimer(ref name)) if name == "bell" => (),
+		other => panic!("Unexpected result {:?}", other)
+	}
+	assert_eq!(timers::now(), clock);
+	assert_eq!(events::current_tick(), tick);
+}
@@ -1,8 +1,5 @@
 #[macro_use]
 extern crate entity_rust;
-#[macro_use]
-extern crate lazy_static;
-extern crate shared_mutex;
 
 use std::collections::HashMap;
 
@@ -0,0 +1,47 @@
+#![cfg(feature = "tracing")]
+
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::{ events, stats };
+use std::time::Duration;
+
+event!{ measured_event, x: i64 }
+
+system!( measured_system {
+	use super::measured_event;
+
+	state { } { }
+
+	on measured_event, {}, {}, (self, data) => {
+	}
+});
+
+#[test]
+fn stats_aggregate_timings_and_triggers() {
+	stats::enable(stats::Config { window: 2, handler_budget: Some(Duration::new(0, 0)) });
+	measured_system::register();
+
+	for tick in 0..3 {
+		measured_event::trigger(tick);
+		measured_event::trigger(tick);
+		events::next_tick();
+		events::run_tick().expect("Tick failed");
+	}
+	stats::disable();
+
+	assert_eq!(stats::trigger_count(measured_event::EVENT_NAME), 6);
+
+	let system = stats::system("stats::measured_system").expect("System was not measured");
+	assert_eq!(system.count, 2);
+	assert!(system.min <= system.avg && system.avg <= system.max);
+	assert!(system.p99 <= system.max);
+
+	let event = stats::event(measured_event::EVENT_NAME).expect("Event was not measured");
+	assert_eq!(event.count, 2);
+	assert!(stats::slow_handlers() > 0);
+	let warnings = stats::warnings();
+	assert_eq!(warnings.len(), 2);
+	assert!(warnings.iter().all(|w| w.system_name == "stats::measured_system" && w.event_name == measured_event::EVENT_NAME));
+	assert!(stats::report().to_string().contains("stats::measured_system"));
+}
@@ -1,14 +1,19 @@
-#![feature(type_macros)]
-
 #[macro_use]
 extern crate entity_rust;
-#[macro_use]
-extern crate lazy_static;
-extern crate shared_mutex;
-extern crate uuid;
 
 event!{ my_event , x: i64, y: i64 }
 event!{ my_event_2 , x: i64, y: i64 }
+event!{ faction_turn , gold: i64 }
+event!{ shipment , cargo: super::Cargo }
+event!{ muster , ship: usize }
+
+tag!{ flagship }
+
+// Event data does not have to be Clone to be delivered to multiple handlers.
+#[derive(PartialEq, Eq)]
+pub struct Cargo {
+	pub crates: Vec<u8>
+}
 
 system!( my_system {
 	use super::my_event;
@@ -31,11 +36,108 @@ system!( my_system {
 	}
 });
 
+system!( faction_system {
+	use super::faction_turn;
+
+	state { gold: i64 } { gold = 0 }
+
+	on faction_turn, {}, {}, (self, data) => {
+		for turn in data.iter() {
+			self.gold += turn.gold;
+		}
+	}
+});
+
+system!( harbour_system {
+	use super::shipment;
+
+	// the address of the data the handler was given
+	state { crates: usize, data_address: usize } { crates = 0; data_address = 0 }
+
+	on shipment, {}, {}, (self, data) => {
+		self.data_address = data.as_ptr() as usize;
+		for delivery in data.iter() {
+			self.crates += delivery.cargo.crates.len();
+		}
+	}
+});
+
+system!( conflicting_system {
+	use super::faction_turn;
+	use super::my_system::position;
+
+	state { } { }
+
+	on faction_turn, { moved: position }, { seen: position }, (self, data) => {
+	}
+});
+
+system!( double_reading_system {
+	use super::faction_turn;
+	use super::my_system::position;
+
+	state { } { }
+
+	on faction_turn, {}, { first: position second: position }, (self, data) => {
+	}
+});
+
+system!( fleet_system {
+	use super::muster;
+	use super::flagship;
+
+	state { flagships: usize } { flagships = 0 }
+
+	on muster, {}, { flagships: flagship }, (self, data) => {
+		for m in data.iter() {
+			if flagships.contains(&m.ship) {
+				self.flagships += 1;
+			}
+		}
+	}
+});
+
+use entity_rust::events;
+use std::sync::Mutex;
+
+// running events drains the queues of every event, so the tests take turns
+static EVENTS: Mutex<()> = Mutex::new(());
+
 #[test]
 fn generates_functions() {
 	my_system::register();
 }
 
+#[test]
+#[should_panic(expected = "more than once")]
+fn reading_and_writing_a_component_is_rejected() {
+	conflicting_system::register();
+}
+
+#[test]
+#[should_panic(expected = "more than once")]
+fn reading_a_component_twice_is_rejected() {
+	double_reading_system::register();
+}
+
+#[test]
+fn instances_have_distinct_state() {
+	let _events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+	let rich = faction_system::register_with_state(faction_system::State { gold: 100 });
+	let poor = faction_system::register();
+
+	faction_turn::trigger(5);
+	events::run_events();
+	assert_eq!(rich.state.read().expect("System state corrupted").gold, 105);
+	assert_eq!(poor.state.read().expect("System state corrupted").gold, 5);
+
+	faction_system::unregister(&poor);
+	faction_turn::trigger(5);
+	events::run_events();
+	assert_eq!(rich.state.read().expect("System state corrupted").gold, 110);
+	assert_eq!(poor.state.read().expect("System state corrupted").gold, 5);
+}
+
 /*
 #[test]
 fn on_event_works() {
@@ -51,4 +153,39 @@ fn on_event_works() {
 	let state = my_system::state.read().expect("System state corrupted");
 	assert!(state.x == 4);
 }
-*/
\ No newline at end of file
+*/
+
+#[test]
+fn event_data_is_shared_between_handlers() {
+	let _events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+	let north = harbour_system::register();
+	let south = harbour_system::register();
+
+	shipment::trigger(Cargo { crates: vec![0; 1024] });
+	events::run_events();
+	assert_eq!(north.state.read().expect("System state corrupted").crates, 1024);
+	assert_eq!(south.state.read().expect("System state corrupted").crates, 1024);
+
+	let north_address = north.state.read().expect("System state corrupted").data_address;
+	let south_address = south.state.read().expect("System state corrupted").data_address;
+	assert!(north_address != 0);
+	assert_eq!(north_address, south_address);
+}
+
+#[test]
+fn tags_filter_handled_entities() {
+	let _events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+	flagship::register();
+	assert!(flagship::add(3));
+	assert!(!flagship::add(3));
+	flagship::add(7);
+	assert!(flagship::remove(7));
+	assert!(flagship::contains(3));
+	assert!(!flagship::contains(7));
+
+	let fleet = fleet_system::register();
+	muster::trigger(3);
+	muster::trigger(7);
+	events::run_events();
+	assert_eq!(fleet.state.read().expect("System state corrupted").flagships, 1);
+}
@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::timers;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration;
+
+event!{ explode, power: i64 }
+
+static FUSES: AtomicUsize = AtomicUsize::new(0);
+static AUTOSAVES: AtomicUsize = AtomicUsize::new(0);
+static CANCELLED: AtomicUsize = AtomicUsize::new(0);
+
+// The timers share one clock, so everything is tested in a single test.
+#[test]
+fn timers_fire_on_schedule() {
+	timers::clear();
+	let second = Duration::from_secs(1);
+
+	let fuse = timers::after(second * 3, || {
+		FUSES.fetch_add(1, Ordering::SeqCst);
+		explode::trigger(10);
+	});
+	timers::every(second * 2, || { AUTOSAVES.fetch_add(1, Ordering::SeqCst); });
+	let cancelled = timers::after(second, || { CANCELLED.fetch_add(1, Ordering::SeqCst); });
+	assert!(cancelled.cancel());
+	assert!(!cancelled.cancel());
+
+	timers::advance(second);
+	timers::advance(second);
+	assert_eq!(FUSES.load(Ordering::SeqCst), 0);
+	assert_eq!(AUTOSAVES.load(Ordering::SeqCst), 1);
+
+	timers::advance(second);
+	assert_eq!(FUSES.load(Ordering::SeqCst), 1);
+	assert!(!fuse.is_active());
+	assert_eq!(explode::THIS_TICK_DATA.read().expect("THIS_TICK_DATA mutex corrupted")[0].power, 10);
+
+	// a large step fires a repeating timer once per interval
+	timers::advance(second * 5);
+	assert_eq!(AUTOSAVES.load(Ordering::SeqCst), 4);
+	assert_eq!(FUSES.load(Ordering::SeqCst), 1);
+	assert_eq!(CANCELLED.load(Ordering::SeqCst), 0);
+	assert_eq!(timers::now(), second * 8);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "tracing")]
+
+#[macro_use]
+extern crate entity_rust;
+
+use entity_rust::{ events, tracing };
+use std::sync::{ Arc, Mutex };
+
+event!{ traced_event, x: i64 }
+
+system!( traced_system {
+	use super::traced_event;
+
+	state { } { }
+
+	on traced_event, {}, {}, (self, data) => {
+	}
+});
+
+struct Collector {
+	spans: Arc<Mutex<Vec<String>>>
+}
+
+impl tracing::Subscriber for Collector {
+	fn handler(&self, span: &tracing::HandlerSpan) {
+		let mut spans = self.spans.lock().unwrap();
+		spans.push(format!("handler {} {} {}", span.system_name, span.event_name, span.data_count));
+	}
+
+	fn event_batch(&self, span: &tracing::BatchSpan) {
+		let mut spans = self.spans.lock().unwrap();
+		spans.push(format!("event {} {}", span.event_name, span.handlers));
+	}
+
+	fn tick(&self, span: &tracing::TickSpan) {
+		let mut spans = self.spans.lock().unwrap();
+		spans.push(format!("tick {}", span.events));
+	}
+}
+
+#[test]
+fn subscribers_receive_spans() {
+	let spans = Arc::new(Mutex::new(vec![]));
+	let subscriber = tracing::add_subscriber(Collector { spans: spans.clone() });
+	traced_system::register();
+
+	traced_event::trigger(1);
+	traced_event::trigger(2);
+	events::run_tick().expect("Tick failed");
+	tracing::remove_subscriber(subscriber);
+
+	let spans = spans.lock().unwrap();
+	assert_eq!(*spans, vec![
+		"handler tracing::traced_system tracing::traced_event 2".to_string(),
+		"event tracing::traced_event 1".to_string(),
+		"tick 2".to_string()
+	]);
+}